@@ -1,13 +1,18 @@
 use clap::{Parser, ValueEnum};
 use std::fs::File;
+use ypbank::compression::decompress_auto;
 use ypbank::error::BankFormatError;
-use ypbank::{CompareResult, CsvFormat, bin_format::BinFormat, compare, txt_format::TxtFormat};
+use ypbank::{
+    CompareResult, CsvFormat, bin_format::BinFormat, compare, json_format::JsonFormat,
+    txt_format::TxtFormat,
+};
 
 #[derive(ValueEnum, Clone)]
 enum Format {
     Csv,
     Txt,
     Binary,
+    Json,
 }
 
 #[derive(Parser)]
@@ -25,8 +30,8 @@ struct Args {
 fn main() -> Result<(), BankFormatError> {
     let args = Args::parse();
 
-    let mut f1 = File::open(&args.file1)?;
-    let mut f2 = File::open(&args.file2)?;
+    let (_, mut f1) = decompress_auto(File::open(&args.file1)?)?;
+    let (_, mut f2) = decompress_auto(File::open(&args.file2)?)?;
 
     let result = match (args.format1, args.format2) {
         (Format::Binary, Format::Csv) => compare::<BinFormat, CsvFormat>(&mut f1, &mut f2)?,
@@ -38,6 +43,13 @@ fn main() -> Result<(), BankFormatError> {
         (Format::Txt, Format::Binary) => compare::<TxtFormat, BinFormat>(&mut f1, &mut f2)?,
         (Format::Txt, Format::Csv) => compare::<TxtFormat, CsvFormat>(&mut f1, &mut f2)?,
         (Format::Txt, Format::Txt) => compare::<TxtFormat, TxtFormat>(&mut f1, &mut f2)?,
+        (Format::Binary, Format::Json) => compare::<BinFormat, JsonFormat>(&mut f1, &mut f2)?,
+        (Format::Csv, Format::Json) => compare::<CsvFormat, JsonFormat>(&mut f1, &mut f2)?,
+        (Format::Txt, Format::Json) => compare::<TxtFormat, JsonFormat>(&mut f1, &mut f2)?,
+        (Format::Json, Format::Binary) => compare::<JsonFormat, BinFormat>(&mut f1, &mut f2)?,
+        (Format::Json, Format::Csv) => compare::<JsonFormat, CsvFormat>(&mut f1, &mut f2)?,
+        (Format::Json, Format::Txt) => compare::<JsonFormat, TxtFormat>(&mut f1, &mut f2)?,
+        (Format::Json, Format::Json) => compare::<JsonFormat, JsonFormat>(&mut f1, &mut f2)?,
     };
 
     match result {
@@ -61,8 +73,14 @@ fn main() -> Result<(), BankFormatError> {
                     "Transaction {} differs between '{}' and '{}':",
                     id, args.file1, args.file2
                 );
-                println!("  {}: {:?}", args.file1, tx1);
-                println!("  {}: {:?}", args.file2, tx2);
+                for field in tx1.differing_fields(&tx2) {
+                    println!(
+                        "  {}: {} -> {}",
+                        field,
+                        tx1.field_string(field),
+                        tx2.field_string(field)
+                    );
+                }
             }
         }
     }
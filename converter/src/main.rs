@@ -1,19 +1,78 @@
-use clap::{Parser, ValueEnum};
+use clap::{Args, Parser, ValueEnum};
 use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use ypbank::compression::{CompressWriter, Compression, decompress_auto};
 use ypbank::error::BankFormatError;
-use ypbank::{CsvFormat, bin_format::BinFormat, convert, txt_format::TxtFormat};
+use ypbank::transform::{SortKey, sort_transactions};
+use ypbank::{
+    BankFormat, CsvFormat, Status, Transaction, TxType, bin_format::BinFormat,
+    json_format::JsonFormat, txt_format::TxtFormat,
+};
 
 #[derive(Parser)]
 #[command(name = "ypbank_converter")]
 struct Cli {
     #[arg(long)]
-    input: std::path::PathBuf,
+    input: PathBuf,
 
     #[arg(long, value_enum)]
     input_format: Format,
 
     #[arg(long, value_enum)]
     output_format: Format,
+
+    /// Write to this file instead of stdout. The file is replaced atomically on success.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Compress the output. Compressed input is detected automatically.
+    #[arg(long, value_enum, default_value = "none")]
+    compress: Compress,
+
+    #[command(flatten)]
+    processing: Processing,
+}
+
+/// What happens to the records between reading and writing.
+#[derive(Args, Default)]
+struct Processing {
+    #[command(flatten)]
+    filter: Filter,
+
+    /// Sort the records before writing. Applied after filtering; ties on timestamp
+    /// are ordered by tx_id, other ties keep the input order.
+    #[arg(long, value_enum)]
+    sort: Option<SortBy>,
+
+    /// Skip records that fail to parse instead of aborting, and list them on stderr.
+    #[arg(long)]
+    lenient: bool,
+}
+
+/// Which records to keep; all of them by default.
+#[derive(Args, Default)]
+struct Filter {
+    /// Keep only records of this type, e.g. TRANSFER.
+    #[arg(long)]
+    filter_type: Option<TxType>,
+
+    /// Keep only records with this status, e.g. SUCCESS.
+    #[arg(long)]
+    filter_status: Option<Status>,
+
+    /// Keep only records with a timestamp at or after this one.
+    #[arg(long, value_name = "TS")]
+    since: Option<i64>,
+}
+
+impl Filter {
+    fn apply(&self, tx: Transaction) -> Option<Transaction> {
+        let keep = self.filter_type.as_ref().is_none_or(|t| *t == tx.tx_type)
+            && self.filter_status.as_ref().is_none_or(|s| *s == tx.status)
+            && self.since.is_none_or(|since| tx.timestamp >= since);
+        keep.then_some(tx)
+    }
 }
 
 #[derive(ValueEnum, Clone)]
@@ -21,20 +80,385 @@ enum Format {
     Csv,
     Txt,
     Bin,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum SortBy {
+    #[value(name = "tx_id")]
+    TxId,
+    Timestamp,
+    Amount,
+}
+
+impl From<SortBy> for SortKey {
+    fn from(s: SortBy) -> Self {
+        match s {
+            SortBy::TxId => SortKey::TxId,
+            SortBy::Timestamp => SortKey::Timestamp,
+            SortBy::Amount => SortKey::Amount,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Compress {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl From<Compress> for Compression {
+    fn from(c: Compress) -> Self {
+        match c {
+            Compress::None => Compression::None,
+            Compress::Gzip => Compression::Gzip,
+            Compress::Zstd => Compression::Zstd,
+        }
+    }
 }
 
 fn main() -> Result<(), BankFormatError> {
     let cli = Cli::parse();
-    let mut input = File::open(&cli.input)?;
-    let mut stdout = std::io::stdout().lock();
-    match (cli.input_format, cli.output_format) {
-        (Format::Csv, Format::Txt) => convert::<CsvFormat, TxtFormat>(&mut input, &mut stdout)?,
-        (Format::Txt, Format::Csv) => convert::<TxtFormat, CsvFormat>(&mut input, &mut stdout)?,
-        (Format::Csv, Format::Bin) => convert::<CsvFormat, BinFormat>(&mut input, &mut stdout)?,
-        (Format::Txt, Format::Bin) => convert::<TxtFormat, BinFormat>(&mut input, &mut stdout)?,
-        (Format::Bin, Format::Csv) => convert::<BinFormat, CsvFormat>(&mut input, &mut stdout)?,
-        (Format::Bin, Format::Txt) => convert::<BinFormat, TxtFormat>(&mut input, &mut stdout)?,
-        _ => println!("input and output formats can not be the same"),
-    };
+    let (_, mut input) = decompress_auto(File::open(&cli.input)?)?;
+    let compression = Compression::from(cli.compress);
+    match &cli.output {
+        Some(path) => write_atomic(path, |out| {
+            run(
+                &cli.input_format,
+                &cli.output_format,
+                &mut input,
+                out,
+                compression,
+                &cli.processing,
+            )
+        }),
+        None => run(
+            &cli.input_format,
+            &cli.output_format,
+            &mut input,
+            std::io::stdout().lock(),
+            compression,
+            &cli.processing,
+        ),
+    }
+}
+
+fn run(
+    input_format: &Format,
+    output_format: &Format,
+    input: &mut impl Read,
+    output: impl Write,
+    compression: Compression,
+    processing: &Processing,
+) -> Result<(), BankFormatError> {
+    let mut output = CompressWriter::new(output, compression)?;
+    convert_formats(input_format, output_format, input, &mut output, processing)?;
+    output.finish()?;
     Ok(())
 }
+
+fn convert_formats(
+    input_format: &Format,
+    output_format: &Format,
+    input: &mut impl Read,
+    output: &mut impl Write,
+    p: &Processing,
+) -> Result<(), BankFormatError> {
+    match (input_format, output_format) {
+        (Format::Csv, Format::Txt) => convert_records::<CsvFormat, TxtFormat>(input, output, p),
+        (Format::Txt, Format::Csv) => convert_records::<TxtFormat, CsvFormat>(input, output, p),
+        (Format::Csv, Format::Bin) => convert_records::<CsvFormat, BinFormat>(input, output, p),
+        (Format::Txt, Format::Bin) => convert_records::<TxtFormat, BinFormat>(input, output, p),
+        (Format::Bin, Format::Csv) => convert_records::<BinFormat, CsvFormat>(input, output, p),
+        (Format::Bin, Format::Txt) => convert_records::<BinFormat, TxtFormat>(input, output, p),
+        (Format::Csv, Format::Json) => convert_records::<CsvFormat, JsonFormat>(input, output, p),
+        (Format::Txt, Format::Json) => convert_records::<TxtFormat, JsonFormat>(input, output, p),
+        (Format::Bin, Format::Json) => convert_records::<BinFormat, JsonFormat>(input, output, p),
+        (Format::Json, Format::Csv) => convert_records::<JsonFormat, CsvFormat>(input, output, p),
+        (Format::Json, Format::Txt) => convert_records::<JsonFormat, TxtFormat>(input, output, p),
+        (Format::Json, Format::Bin) => convert_records::<JsonFormat, BinFormat>(input, output, p),
+        _ => Err(BankFormatError::UnsupportedFormat(
+            "input and output formats can not be the same".into(),
+        )),
+    }
+}
+
+/// Read records with `From`, filter and sort them as `p` says and write them with `To`.
+fn convert_records<From: BankFormat, To: BankFormat>(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    p: &Processing,
+) -> Result<(), BankFormatError> {
+    let mut transactions = Vec::new();
+    if p.lenient {
+        let (parsed, skipped) = From::read_all_lenient(input);
+        if !skipped.is_empty() {
+            eprintln!("skipped {} malformed record(s):", skipped.len());
+            for (index, e) in &skipped {
+                eprintln!("  #{index}: {e}");
+            }
+        }
+        transactions.extend(parsed.into_iter().filter_map(|tx| p.filter.apply(tx)));
+    } else {
+        for tx in From::read_iter(input) {
+            transactions.extend(p.filter.apply(tx?));
+        }
+    }
+    if let Some(sort) = p.sort {
+        sort_transactions(&mut transactions, sort.into());
+    }
+    To::write_all(output, &transactions)
+}
+
+/// Run `f` against a temporary file next to `path` and rename it over `path` on success.
+///
+/// On failure the temporary file is removed, so an existing file at `path` is left untouched.
+fn write_atomic(
+    path: &Path,
+    f: impl FnOnce(&mut File) -> Result<(), BankFormatError>,
+) -> Result<(), BankFormatError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        BankFormatError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid output path: {}", path.display()),
+        ))
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = File::create(&tmp_path)
+        .map_err(BankFormatError::Io)
+        .and_then(|mut file| {
+            f(&mut file)?;
+            file.sync_all().map_err(BankFormatError::Io)
+        })
+        .and_then(|_| std::fs::rename(&tmp_path, path).map_err(BankFormatError::Io));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use ypbank::BankFormat;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ypbank_converter_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_atomic_success() {
+        let dir = temp_dir("success");
+        let path = dir.join("out.txt");
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+
+        write_atomic(&path, |out| {
+            run(
+                &Format::Csv,
+                &Format::Txt,
+                &mut Cursor::new(csv),
+                out,
+                Compression::None,
+                &Processing::default(),
+            )
+        })
+        .unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("TX_ID: 1"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_failure_leaves_no_partial_output() {
+        let dir = temp_dir("failure");
+        let existing = dir.join("existing.txt");
+        let missing = dir.join("missing.txt");
+        std::fs::write(&existing, "good output").unwrap();
+
+        // the first record converts fine, the second one fails to parse
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                   2,DEPOSIT,0,42,notanumber,1234567890,SUCCESS,test\n";
+
+        for path in [&existing, &missing] {
+            let result = write_atomic(path, |out| {
+                run(
+                    &Format::Csv,
+                    &Format::Txt,
+                    &mut Cursor::new(csv),
+                    out,
+                    Compression::None,
+                    &Processing::default(),
+                )
+            });
+            assert!(result.is_err());
+        }
+
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "good output");
+        assert!(!missing.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_filter_flags() {
+        let cli = Cli::try_parse_from([
+            "ypbank_converter",
+            "--input",
+            "in.csv",
+            "--input-format",
+            "csv",
+            "--output-format",
+            "json",
+            "--filter-type",
+            "TRANSFER",
+            "--filter-status",
+            "SUCCESS",
+            "--since",
+            "200",
+        ])
+        .unwrap();
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,TRANSFER,10,20,500,100,SUCCESS,early\n\
+                   2,TRANSFER,10,20,500,200,SUCCESS,kept\n\
+                   3,TRANSFER,10,20,500,300,FAILURE,failed\n\
+                   4,DEPOSIT,0,20,500,400,SUCCESS,deposit\n";
+
+        let mut out = Vec::new();
+        let input = &mut Cursor::new(csv);
+        run(
+            &Format::Csv,
+            &Format::Txt,
+            input,
+            &mut out,
+            Compression::None,
+            &cli.processing,
+        )
+        .unwrap();
+        let kept = TxtFormat::read_all(&mut out.as_slice()).unwrap();
+        assert_eq!(kept.iter().map(|tx| tx.tx_id).collect::<Vec<_>>(), vec![2]);
+
+        let none = Processing {
+            filter: Filter {
+                since: Some(1000),
+                ..Filter::default()
+            },
+            ..Processing::default()
+        };
+        let mut out = Vec::new();
+        run(
+            &Format::Csv,
+            &Format::Json,
+            &mut Cursor::new(csv),
+            &mut out,
+            Compression::None,
+            &none,
+        )
+        .unwrap();
+        assert!(
+            JsonFormat::read_all(&mut out.as_slice())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_sort_flag() {
+        let cli = Cli::try_parse_from([
+            "ypbank_converter",
+            "--input",
+            "in.csv",
+            "--input-format",
+            "csv",
+            "--output-format",
+            "txt",
+            "--filter-status",
+            "SUCCESS",
+            "--sort",
+            "timestamp",
+        ])
+        .unwrap();
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   3,DEPOSIT,0,20,500,200,SUCCESS,c\n\
+                   1,DEPOSIT,0,20,500,200,SUCCESS,a\n\
+                   4,DEPOSIT,0,20,500,50,FAILURE,d\n\
+                   2,DEPOSIT,0,20,500,100,SUCCESS,b\n";
+
+        let mut out = Vec::new();
+        run(
+            &Format::Csv,
+            &Format::Txt,
+            &mut Cursor::new(csv),
+            &mut out,
+            Compression::None,
+            &cli.processing,
+        )
+        .unwrap();
+        let sorted = TxtFormat::read_all(&mut out.as_slice()).unwrap();
+        assert_eq!(
+            sorted.iter().map(|tx| tx.tx_id).collect::<Vec<_>>(),
+            vec![2, 1, 3]
+        );
+
+        assert!(
+            Cli::try_parse_from([
+                "c",
+                "--input",
+                "x",
+                "--input-format",
+                "csv",
+                "--output-format",
+                "bin",
+                "--sort",
+                "tx_id",
+            ])
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_lenient_skips_bad_rows() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                   2,DEPOSIT,0,42,notanumber,1234567890,SUCCESS,test\n\
+                   3,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+        let convert = |processing: &Processing| {
+            let mut out = Vec::new();
+            run(
+                &Format::Csv,
+                &Format::Txt,
+                &mut Cursor::new(csv),
+                &mut out,
+                Compression::None,
+                processing,
+            )
+            .map(|_| out)
+        };
+
+        assert!(convert(&Processing::default()).is_err());
+        let lenient = Processing {
+            lenient: true,
+            ..Processing::default()
+        };
+        let out = convert(&lenient).unwrap();
+        let kept = TxtFormat::read_all(&mut out.as_slice()).unwrap();
+        assert_eq!(
+            kept.iter().map(|tx| tx.tx_id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Read-only reports computed over a set of transaction records.
+use crate::{Status, Transaction, TxId, TxType};
+use std::collections::{BTreeMap, HashMap};
+
+/// Milliseconds in a UTC calendar day.
+pub const MS_PER_DAY: i64 = 86_400_000;
+
+/// Group transaction indices into fixed-size time windows.
+///
+/// The key of each bucket is the window start in epoch milliseconds, i.e. the
+/// timestamp floored to a multiple of `window_ms` (also for negative timestamps).
+/// Indices within a bucket are in input order.
+///
+/// # Panics
+/// Panics if `window_ms` is not positive.
+pub fn bucket_by_time(txs: &[Transaction], window_ms: i64) -> BTreeMap<i64, Vec<usize>> {
+    assert!(
+        window_ms > 0,
+        "window_ms must be positive, got {}",
+        window_ms
+    );
+
+    let mut buckets: BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+    for (i, tx) in txs.iter().enumerate() {
+        let start = tx.timestamp.div_euclid(window_ms) * window_ms;
+        buckets.entry(start).or_default().push(i);
+    }
+    buckets
+}
+
+/// Byte-length statistics over transaction descriptions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DescStats {
+    /// Shortest description length in bytes.
+    pub min_len: usize,
+    /// Longest description length in bytes.
+    pub max_len: usize,
+    /// Mean description length in bytes.
+    pub mean_len: f64,
+    /// Id of the record with the longest description (the first one on ties).
+    /// `None` for an empty input.
+    pub longest_tx_id: Option<TxId>,
+}
+
+/// Compute min/max/mean description byte lengths and the id of the longest one.
+pub fn description_stats(txs: &[Transaction]) -> DescStats {
+    let Some(first) = txs.first() else {
+        return DescStats::default();
+    };
+
+    let mut stats = DescStats {
+        min_len: first.description.len(),
+        max_len: first.description.len(),
+        mean_len: 0.0,
+        longest_tx_id: Some(first.tx_id),
+    };
+    let mut total = 0usize;
+    for tx in txs {
+        let len = tx.description.len();
+        total += len;
+        stats.min_len = stats.min_len.min(len);
+        if len > stats.max_len {
+            stats.max_len = len;
+            stats.longest_tx_id = Some(tx.tx_id);
+        }
+    }
+    stats.mean_len = total as f64 / txs.len() as f64;
+    stats
+}
+
+/// Ids of `TRANSFER` records whose sender and recipient are the same user.
+pub fn find_self_transfers(txs: &[Transaction]) -> Vec<TxId> {
+    txs.iter()
+        .filter(|tx| tx.tx_type == TxType::Transfer && tx.from_user_id == tx.to_user_id)
+        .map(|tx| tx.tx_id)
+        .collect()
+}
+
+/// Check that successful transfers conserve money among real users.
+///
+/// Every successful `TRANSFER` debits its sender and credits its recipient; the
+/// system user `0` is treated as external, as are deposits and withdrawals, which are
+/// ignored. In a closed ledger the net effect over all real users is zero. Otherwise
+/// the net (credits minus debits) is returned, e.g. for transfers that leak money to
+/// or from the system id. The net is widened to `i128` so that it can not overflow.
+pub fn check_conservation(txs: &[Transaction]) -> Result<(), i128> {
+    let mut net = 0i128;
+    for tx in txs {
+        if tx.tx_type != TxType::Transfer || tx.status != Status::Success {
+            continue;
+        }
+        if tx.from_user_id != 0 {
+            net -= i128::from(tx.amount);
+        }
+        if tx.to_user_id != 0 {
+            net += i128::from(tx.amount);
+        }
+    }
+    if net == 0 { Ok(()) } else { Err(net) }
+}
+
+/// Number and summed amount of the records of one [`TxType`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct TypeTotals {
+    /// Number of records.
+    pub count: usize,
+    /// Sum of their amounts, widened so that no sum of `i64` amounts overflows.
+    pub amount: i128,
+}
+
+/// Aggregates over a set of transactions, computed by [`summarize`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Summary {
+    /// Total number of records.
+    pub count: usize,
+    /// Count and summed amount per type, over all records.
+    pub by_type: HashMap<TxType, TypeTotals>,
+    /// Number of records per status.
+    pub by_status: HashMap<Status, usize>,
+    /// Net balance per user: credits as `to_user_id` minus debits as `from_user_id`.
+    /// The system id `0` and `FAILURE` records are left out. Widened to `i128` like
+    /// [`TypeTotals::amount`].
+    pub balances: HashMap<i64, i128>,
+}
+
+/// Compute counts per type and status and the net balance of every user.
+///
+/// Every record is counted, but `FAILURE` records do not move money, so they are
+/// skipped in [`Summary::balances`].
+pub fn summarize(txs: &[Transaction]) -> Summary {
+    let mut summary = Summary {
+        count: txs.len(),
+        ..Summary::default()
+    };
+    for tx in txs {
+        let totals = summary.by_type.entry(tx.tx_type.clone()).or_default();
+        totals.count += 1;
+        totals.amount += i128::from(tx.amount);
+        *summary.by_status.entry(tx.status.clone()).or_default() += 1;
+
+        if tx.status == Status::Failure {
+            continue;
+        }
+        if tx.from_user_id != 0 {
+            *summary.balances.entry(tx.from_user_id).or_default() -= i128::from(tx.amount);
+        }
+        if tx.to_user_id != 0 {
+            *summary.balances.entry(tx.to_user_id).or_default() += i128::from(tx.amount);
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx_at(tx_id: u64, timestamp: i64) -> Transaction {
+        Transaction {
+            tx_id,
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 42,
+            amount: 1000,
+            timestamp,
+            status: Status::Success,
+            description: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bucket_by_time_hourly() {
+        const HOUR: i64 = 3_600_000;
+        let txs = vec![
+            tx_at(1, 1_700_000_000_000),
+            tx_at(2, 1_700_000_000_000 + HOUR),
+            tx_at(3, 1_700_000_000_000 + 60_000),
+            tx_at(4, -1),
+        ];
+
+        let buckets = bucket_by_time(&txs, HOUR);
+        let first = 1_700_000_000_000 / HOUR * HOUR;
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[&first], vec![0, 2]);
+        assert_eq!(buckets[&(first + HOUR)], vec![1]);
+        assert_eq!(buckets[&-HOUR], vec![3]);
+    }
+
+    #[test]
+    fn test_description_stats() {
+        let mut txs = vec![tx_at(1, 0), tx_at(2, 0), tx_at(3, 0)];
+        txs[0].description = "rent".into();
+        txs[1].description = "groceries for the week".into();
+        txs[2].description = String::new();
+
+        let stats = description_stats(&txs);
+        assert_eq!(stats.min_len, 0);
+        assert_eq!(stats.max_len, 22);
+        assert_eq!(stats.longest_tx_id, Some(2));
+        assert!((stats.mean_len - 26.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(description_stats(&[]), DescStats::default());
+    }
+
+    fn transfer(tx_id: u64, from_user_id: i64, to_user_id: i64) -> Transaction {
+        Transaction {
+            tx_type: TxType::Transfer,
+            from_user_id,
+            to_user_id,
+            ..tx_at(tx_id, 0)
+        }
+    }
+
+    #[test]
+    fn test_find_self_transfers() {
+        let txs = vec![transfer(1, 10, 20), transfer(2, 10, 10), tx_at(3, 0)];
+        assert_eq!(find_self_transfers(&txs), vec![2]);
+    }
+
+    #[test]
+    fn test_check_conservation() {
+        let mut txs = vec![transfer(1, 10, 20), transfer(2, 20, 30), tx_at(3, 0)];
+        assert_eq!(check_conservation(&txs), Ok(()));
+
+        let mut failed = transfer(4, 0, 10);
+        failed.status = Status::Failure;
+        txs.push(failed);
+        assert_eq!(check_conservation(&txs), Ok(()));
+
+        let mut leak = transfer(5, 30, 0);
+        leak.amount = 250;
+        txs.push(leak);
+        assert_eq!(check_conservation(&txs), Err(-250));
+    }
+
+    #[test]
+    fn test_check_conservation_large_amounts() {
+        let mut txs = vec![transfer(1, 10, 0), transfer(2, 20, 0)];
+        for tx in &mut txs {
+            tx.amount = i64::MAX;
+        }
+        assert_eq!(check_conservation(&txs), Err(-2 * i128::from(i64::MAX)));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut pay = transfer(2, 42, 7);
+        pay.amount = 300;
+        let mut failed = transfer(3, 42, 7);
+        failed.status = Status::Failure;
+        let summary = summarize(&[tx_at(1, 0), pay, failed]);
+
+        assert_eq!(summary.count, 3);
+        assert_eq!(
+            summary.by_type[&TxType::Transfer],
+            TypeTotals {
+                count: 2,
+                amount: 1300
+            }
+        );
+        assert_eq!(summary.by_type[&TxType::Deposit].count, 1);
+        assert_eq!(summary.by_status[&Status::Success], 2);
+        assert_eq!(summary.by_status[&Status::Failure], 1);
+        assert_eq!(summary.balances, HashMap::from([(42, 700), (7, 300)]));
+    }
+
+    #[test]
+    fn test_summarize_large_amounts() {
+        let mut txs = vec![tx_at(1, 0), tx_at(2, 0)];
+        for tx in &mut txs {
+            tx.amount = i64::MAX;
+        }
+        let summary = summarize(&txs);
+        let expected = 2 * i128::from(i64::MAX);
+        assert_eq!(summary.by_type[&TxType::Deposit].amount, expected);
+        assert_eq!(summary.balances, HashMap::from([(42, expected)]));
+    }
+}
@@ -1,190 +1,1056 @@
 use crate::error::BankFormatError;
+use crate::stream::CountingReader;
 use crate::{BankFormat, Status, Transaction, TxId, TxType};
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 const MAGIC: [u8; 4] = [0x59, 0x50, 0x42, 0x4E]; // 'YPBN'
+const DELTA_MAGIC: [u8; 4] = [0x59, 0x50, 0x42, 0x44]; // 'YPBD'
+const FOOTER_MAGIC: [u8; 4] = [0x59, 0x50, 0x42, 0x46]; // 'YPBF'
+const FOOTER_LEN: usize = 64;
+const FILE_MAGIC: [u8; 4] = [0x59, 0x50, 0x42, 0x48]; // 'YPBH'
+const FILE_HEADER_LEN: usize = 6; // magic + version + endianness flag
+const FORMAT_VERSION: u8 = 1;
 const MAX_DESC_LEN: usize = 4096;
+const HEADER_LEN: usize = 8; // magic + record size
+const MIN_RECORD_SIZE: u32 = 46;
+const MIN_DELTA_RECORD_SIZE: u32 = 39; // 1-byte varint in place of the 8-byte tx_id
+const MAX_VARINT_LEN: usize = 10;
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub struct BinFormat;
 
+/// How [`BinFormat`] treats bytes after the last complete record.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum TrailingPolicy {
+    /// Fail with [`BankFormatError::InvalidBinary`] if anything but a complete record follows.
+    #[default]
+    Reject,
+    /// Stop reading at the first position that does not start with the record magic.
+    Ignore,
+}
+
+/// Byte order of the integer fields of binary records.
+///
+/// Files written by [`BinFormat::write_all`] start with a file header: the magic
+/// `YPBH`, a `u8` format version and a `u8` endianness flag (`0` big, `1` little).
+/// Readers decode the records that follow accordingly. Streams without a header,
+/// as written by earlier versions, are read as big-endian.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Endian {
+    /// Most significant byte first.
+    #[default]
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+impl Endian {
+    fn from_flag(flag: u8) -> Result<Self, BankFormatError> {
+        match flag {
+            0 => Ok(Endian::Big),
+            1 => Ok(Endian::Little),
+            other => Err(BankFormatError::InvalidBinary(format!(
+                "unknown endianness flag {other}"
+            ))),
+        }
+    }
+
+    fn flag(self) -> u8 {
+        match self {
+            Endian::Big => 0,
+            Endian::Little => 1,
+        }
+    }
+
+    fn u32(self, b: [u8; 4]) -> u32 {
+        match self {
+            Endian::Big => u32::from_be_bytes(b),
+            Endian::Little => u32::from_le_bytes(b),
+        }
+    }
+
+    fn u64(self, b: [u8; 8]) -> u64 {
+        match self {
+            Endian::Big => u64::from_be_bytes(b),
+            Endian::Little => u64::from_le_bytes(b),
+        }
+    }
+
+    fn u32_bytes(self, v: u32) -> [u8; 4] {
+        match self {
+            Endian::Big => v.to_be_bytes(),
+            Endian::Little => v.to_le_bytes(),
+        }
+    }
+
+    fn u64_bytes(self, v: u64) -> [u8; 8] {
+        match self {
+            Endian::Big => v.to_be_bytes(),
+            Endian::Little => v.to_le_bytes(),
+        }
+    }
+}
+
+/// Options controlling how [`BinFormat`] reads and writes records.
+#[derive(Debug, Clone, Default)]
+pub struct BinOptions {
+    /// Handling of trailing bytes. Defaults to [`TrailingPolicy::Reject`].
+    pub trailing: TrailingPolicy,
+    /// Preserve unknown tx_type/status bytes as [`TxType::Other`]/[`Status::Other`]
+    /// holding the decimal code, instead of failing. Defaults to `false`.
+    pub lenient_enums: bool,
+    /// Write records with a delta-encoded tx_id. Defaults to `false`.
+    ///
+    /// Delta records start with the magic `YPBD` and store the id as a zigzag varint
+    /// of the difference from the previous record's id (zero before the first record)
+    /// instead of a fixed 8-byte value. Readers always accept both record kinds, so
+    /// this only affects writing. The previous id carries across the whole stream, so
+    /// separately written delta files can not simply be concatenated.
+    pub delta_ids: bool,
+    /// Byte order of the integer fields when writing. Defaults to [`Endian::Big`].
+    ///
+    /// Readers take the byte order from the file header, so this only affects writing.
+    pub endian: Endian,
+}
+
+/// Aggregates stored in the optional fixed-size footer written by
+/// [`BinFormat::write_all_with_footer`].
+///
+/// The footer is the last [`FOOTER_LEN`] (64) bytes of the file: the magic `YPBF`,
+/// then `count` (u64), `min_timestamp`, `max_timestamp` and `total_success_amount`
+/// (i64), all big-endian, zero-padded to 64 bytes. Readers stop at the footer.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct BinFooter {
+    /// Number of records in the file.
+    pub count: u64,
+    /// Smallest record timestamp, `0` for an empty file.
+    pub min_timestamp: i64,
+    /// Largest record timestamp, `0` for an empty file.
+    pub max_timestamp: i64,
+    /// Sum of the amounts of `SUCCESS` records.
+    pub total_success_amount: i64,
+}
+
+impl BinFooter {
+    /// Compute the footer aggregates for `records`.
+    ///
+    /// Returns [`BankFormatError::InvalidBinary`] if the total of the `SUCCESS`
+    /// amounts does not fit in an `i64`.
+    pub fn summarize(records: &[Transaction]) -> Result<Self, BankFormatError> {
+        let timestamps = records.iter().map(|tx| tx.timestamp);
+        let total_success_amount = records
+            .iter()
+            .filter(|tx| tx.status == Status::Success)
+            .try_fold(0i64, |total, tx| total.checked_add(tx.amount))
+            .ok_or_else(|| {
+                BankFormatError::InvalidBinary(
+                    "total_success_amount overflows the footer".to_string(),
+                )
+            })?;
+        Ok(BinFooter {
+            count: records.len() as u64,
+            min_timestamp: timestamps.clone().min().unwrap_or(0),
+            max_timestamp: timestamps.max().unwrap_or(0),
+            total_success_amount,
+        })
+    }
+
+    fn to_bytes(self) -> [u8; FOOTER_LEN] {
+        let mut buf = [0u8; FOOTER_LEN];
+        buf[..4].copy_from_slice(&FOOTER_MAGIC);
+        buf[4..12].copy_from_slice(&self.count.to_be_bytes());
+        buf[12..20].copy_from_slice(&self.min_timestamp.to_be_bytes());
+        buf[20..28].copy_from_slice(&self.max_timestamp.to_be_bytes());
+        buf[28..36].copy_from_slice(&self.total_success_amount.to_be_bytes());
+        buf
+    }
+
+    /// Decode a footer, or `None` if `buf` does not hold one.
+    fn from_bytes(buf: &[u8; FOOTER_LEN]) -> Option<Self> {
+        if buf[..4] != FOOTER_MAGIC || buf[36..].iter().any(|&b| b != 0) {
+            return None;
+        }
+        let word = |at: usize| <[u8; 8]>::try_from(&buf[at..at + 8]).unwrap();
+        Some(BinFooter {
+            count: u64::from_be_bytes(word(4)),
+            min_timestamp: i64::from_be_bytes(word(12)),
+            max_timestamp: i64::from_be_bytes(word(20)),
+            total_success_amount: i64::from_be_bytes(word(28)),
+        })
+    }
+}
+
+/// The result of [`BinFormat::read_all_raw`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RawRecords {
+    /// Each transaction with the exact bytes of its record, in input order.
+    pub records: Vec<(Transaction, Vec<u8>)>,
+    /// The bytes after the last record: a file header not followed by a record, a
+    /// [`BinFooter`] and anything following it.
+    pub trailing: Vec<u8>,
+}
+
+impl RawRecords {
+    /// Concatenate all bytes in input order, reproducing the input.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.records.into_iter().flat_map(|(_, raw)| raw).collect();
+        bytes.extend(self.trailing);
+        bytes
+    }
+}
+
+/// The kind of record announced by its magic.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum RecordKind {
+    /// `YPBN`: absolute 8-byte tx_id.
+    Plain,
+    /// `YPBD`: varint tx_id delta.
+    Delta,
+}
+
+impl RecordKind {
+    fn min_record_size(self) -> u32 {
+        match self {
+            RecordKind::Plain => MIN_RECORD_SIZE,
+            RecordKind::Delta => MIN_DELTA_RECORD_SIZE,
+        }
+    }
+}
+
 impl BankFormat for BinFormat {
-    fn read_all<R: Read>(r: &mut R) -> Result<Vec<Transaction>, BankFormatError> {
-        let mut transactions = Vec::new();
+    fn read_iter<R: Read>(r: R) -> impl Iterator<Item = Result<Transaction, BankFormatError>> {
+        BinFormat::read_iter_with(r, BinOptions::default())
+    }
+
+    fn write_all<W: Write>(w: &mut W, records: &[Transaction]) -> Result<(), BankFormatError> {
+        BinFormat::write_all_with(w, records, &BinOptions::default())
+    }
+}
+
+impl BinFormat {
+    /// Write all transactions using the given [`BinOptions`].
+    ///
+    /// The records are preceded by a file header declaring the format version and
+    /// byte order, also when `records` is empty. Each record is serialized into a
+    /// reused buffer and handed to `w` in a single `write_all` call, so unbuffered
+    /// writers see one write per record.
+    pub fn write_all_with<W: Write>(
+        w: &mut W,
+        records: &[Transaction],
+        opts: &BinOptions,
+    ) -> Result<(), BankFormatError> {
+        write_file_header(w, opts.endian)?;
+
+        let mut prev_id: TxId = 0;
+        let mut buf: Vec<u8> = Vec::with_capacity(HEADER_LEN + MIN_RECORD_SIZE as usize);
+        for tx in records {
+            encode_record(&mut buf, tx, opts, &mut prev_id)?;
+            w.write_all(&buf).map_err(BankFormatError::Io)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write all transactions followed by a [`BinFooter`] summarizing them.
+    ///
+    /// Nothing is written if the footer can not be computed, see
+    /// [`BinFooter::summarize`].
+    pub fn write_all_with_footer<W: Write>(
+        w: &mut W,
+        records: &[Transaction],
+    ) -> Result<(), BankFormatError> {
+        let footer = BinFooter::summarize(records)?;
+        BinFormat::write_all(w, records)?;
+        w.write_all(&footer.to_bytes())?;
+        Ok(())
+    }
+
+    /// Read the [`BinFooter`] from the end of `r` without reading the records.
+    ///
+    /// Returns `Ok(None)` if the stream does not end with a footer. The stream
+    /// position is left after the footer.
+    pub fn read_footer<R: Read + Seek>(r: &mut R) -> Result<Option<BinFooter>, BankFormatError> {
+        let len = r.seek(SeekFrom::End(0))?;
+        if len < FOOTER_LEN as u64 {
+            return Ok(None);
+        }
+        r.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let mut buf = [0u8; FOOTER_LEN];
+        r.read_exact(&mut buf)?;
+        Ok(BinFooter::from_bytes(&buf))
+    }
+
+    /// Read all transactions using the given [`BinOptions`].
+    pub fn read_all_with<R: Read>(
+        r: &mut R,
+        opts: &BinOptions,
+    ) -> Result<Vec<Transaction>, BankFormatError> {
+        BinFormat::read_iter_with(r, opts.clone()).collect()
+    }
+
+    /// Stream transactions one record at a time using the given [`BinOptions`].
+    pub fn read_iter_with<R: Read>(r: R, opts: BinOptions) -> BinReader<R> {
+        BinReader {
+            r: CountingReader::new(r),
+            opts,
+            endian: Endian::Big,
+            prev_id: 0,
+            records: 0,
+            done: false,
+        }
+    }
+
+    /// Follow a growing binary stream, calling `on_tx` for every complete record.
+    ///
+    /// When the reader hits EOF the follower sleeps and retries instead of returning,
+    /// so records appended later by a writer are picked up. A partially written record
+    /// is buffered until it is complete. Returns once `stop` is set.
+    pub fn follow<R: Read>(
+        r: &mut R,
+        stop: &AtomicBool,
+        mut on_tx: impl FnMut(Transaction),
+    ) -> Result<(), BankFormatError> {
+        let mut pending: Vec<u8> = Vec::new();
+        let mut chunk = [0u8; 8192];
+        let mut prev_id: TxId = 0;
+        let mut endian = Endian::Big;
 
         loop {
-            let mut magic = [0u8; 4];
-            match r.read_exact(&mut magic) {
-                Ok(_) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            while pending.len() >= HEADER_LEN {
+                if pending[..4] == FOOTER_MAGIC {
+                    if pending.len() < FOOTER_LEN {
+                        break;
+                    }
+                    pending.drain(..FOOTER_LEN);
+                    continue;
+                }
+                if pending[..4] == FILE_MAGIC {
+                    endian = parse_file_header([pending[4], pending[5]])?;
+                    pending.drain(..FILE_HEADER_LEN);
+                    continue;
+                }
+                let min_size = check_magic(&pending[..4])?.min_record_size();
+                let record_size = endian.u32(pending[4..8].try_into().unwrap());
+                if record_size < min_size {
+                    return Err(BankFormatError::InvalidBinary(format!(
+                        "record_size {} is too small, minimum is {} bytes",
+                        record_size, min_size
+                    )));
+                }
+                let total = HEADER_LEN + record_size as usize;
+                if pending.len() < total {
+                    break;
+                }
+
+                let tx = decode_record(&pending[..total], endian, prev_id)?;
+                prev_id = tx.tx_id;
+                pending.drain(..total);
+                on_tx(tx);
+            }
+
+            if stop.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            match r.read(&mut chunk) {
+                Ok(0) => std::thread::sleep(FOLLOW_POLL_INTERVAL),
+                Ok(n) => pending.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
                 Err(e) => return Err(BankFormatError::Io(e)),
             }
+        }
+    }
+
+    /// Read all transactions, pairing each with the exact bytes of its record.
+    ///
+    /// [`RawRecords::into_bytes`] reproduces the input, so a file can be re-emitted byte
+    /// for byte regardless of how [`BinFormat::write_all`] encodes records. A file
+    /// header is included in the bytes of the record that follows it. A file header
+    /// with no record after it, a [`BinFooter`] and any bytes after that are kept in
+    /// [`RawRecords::trailing`].
+    pub fn read_all_raw<R: Read>(r: &mut R) -> Result<RawRecords, BankFormatError> {
+        let mut records = Vec::new();
+        let mut trailing = Vec::new();
+        let mut prev_id: TxId = 0;
+        let mut endian = Endian::Big;
+        let mut raw = Vec::new();
+        loop {
+            let start = raw.len();
+            let mut magic = [0u8; 4];
+            let n = read_full(r, &mut magic)?;
+            // a file header with no record after it is kept as trailing bytes
+            if n == 0 {
+                trailing.append(&mut raw);
+                break;
+            }
+            if magic == FOOTER_MAGIC {
+                trailing.append(&mut raw);
+                trailing.extend_from_slice(&read_footer_rest(r)?.to_bytes());
+                r.read_to_end(&mut trailing)?;
+                break;
+            }
+            raw.extend_from_slice(&magic[..n]);
+            if magic == FILE_MAGIC {
+                let mut rest = [0u8; FILE_HEADER_LEN - 4];
+                r.read_exact(&mut rest)?;
+                endian = parse_file_header(rest)?;
+                raw.extend_from_slice(&rest);
+                continue;
+            }
+            check_magic(&magic[..n])?;
+
+            let mut size = [0u8; 4];
+            if read_full(r, &mut size)? < size.len() {
+                return Err(BankFormatError::InvalidBinary(
+                    "unexpected end of record header".into(),
+                ));
+            }
+            raw.extend_from_slice(&size);
+            r.take(u64::from(endian.u32(size))).read_to_end(&mut raw)?;
+            let tx = decode_record(&raw[start..], endian, prev_id)?;
+            prev_id = tx.tx_id;
+            records.push((tx, std::mem::take(&mut raw)));
+        }
+        Ok(RawRecords { records, trailing })
+    }
+
+    /// Read exactly one record from `r`.
+    ///
+    /// Returns `Ok(None)` at a clean EOF, i.e. when no byte of a new record is available,
+    /// and `Ok(Some(tx))` otherwise. A partial record is an error. The reader is left
+    /// positioned right after the record. A delta-encoded id is resolved against `0`,
+    /// as no preceding record is known; use [`BinFormat::read_all`] for delta streams.
+    ///
+    /// A file header before the record is skipped. As the byte order is not carried
+    /// between calls, only big-endian records are supported; use
+    /// [`BinFormat::read_iter`] for little-endian files.
+    pub fn read_one<R: Read>(r: &mut R) -> Result<Option<Transaction>, BankFormatError> {
+        let mut magic = [0u8; 4];
+        let mut n = read_full(r, &mut magic)?;
+        if n == magic.len() && magic == FILE_MAGIC {
+            if read_file_header(r)? != Endian::Big {
+                return Err(BankFormatError::InvalidBinary(
+                    "read_one does not support little-endian records".into(),
+                ));
+            }
+            n = read_full(r, &mut magic)?;
+        }
+        if n == 0 {
+            return Ok(None);
+        }
+        if magic == FOOTER_MAGIC {
+            read_footer_rest(r)?;
+            return Ok(None);
+        }
+        let kind = check_magic(&magic[..n])?;
+        BinFormat::read_body(r, &BinOptions::default(), Endian::Big, kind, 0).map(Some)
+    }
 
-            if magic != MAGIC {
+    /// Read the remainder of a record after its magic.
+    fn read_body<R: Read>(
+        r: &mut R,
+        opts: &BinOptions,
+        endian: Endian,
+        kind: RecordKind,
+        prev_id: TxId,
+    ) -> Result<Transaction, BankFormatError> {
+        // read record size
+        let mut buf4 = [0u8; 4];
+        r.read_exact(&mut buf4).map_err(BankFormatError::Io)?;
+        let record_size = endian.u32(buf4);
+        if record_size < kind.min_record_size() {
+            return Err(BankFormatError::InvalidBinary(format!(
+                "record_size {} is too small, minimum is {} bytes",
+                record_size,
+                kind.min_record_size()
+            )));
+        }
+
+        // TX_ID
+        let mut buf8 = [0u8; 8];
+        let tx_id = match kind {
+            RecordKind::Plain => {
+                r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
+                endian.u64(buf8)
+            }
+            RecordKind::Delta => {
+                let zigzag = read_varint(r)?;
+                let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+                prev_id.wrapping_add_signed(delta)
+            }
+        };
+
+        // TX_TYPE
+        let mut buf1 = [0u8; 1];
+        r.read_exact(&mut buf1).map_err(BankFormatError::Io)?;
+        let tx_type = match buf1[0] {
+            0 => TxType::Deposit,
+            1 => TxType::Transfer,
+            2 => TxType::Withdrawal,
+            other if opts.lenient_enums => TxType::Other(other.to_string()),
+            other => {
                 return Err(BankFormatError::InvalidBinary(format!(
-                    "invalid magic: {:?}",
-                    magic
+                    "unknown tx_type byte: {}",
+                    other
                 )));
             }
+        };
+
+        // FROM_USER_ID
+        r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
+        let from_user_id = endian.u64(buf8) as i64;
+
+        // TO_USER_ID
+        r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
+        let to_user_id = endian.u64(buf8) as i64;
 
-            // read record size
-            let mut buf4 = [0u8; 4];
-            r.read_exact(&mut buf4).map_err(BankFormatError::Io)?;
-            let record_size = u32::from_be_bytes(buf4);
-            if record_size < 46 {
+        // AMOUNT
+        r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
+        let amount = endian.u64(buf8) as i64;
+
+        // TIMESTAMP
+        r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
+        let timestamp = endian.u64(buf8) as i64;
+
+        // STATUS
+        r.read_exact(&mut buf1).map_err(BankFormatError::Io)?;
+        let status = match buf1[0] {
+            0 => Status::Success,
+            1 => Status::Failure,
+            2 => Status::Pending,
+            other if opts.lenient_enums => Status::Other(other.to_string()),
+            other => {
                 return Err(BankFormatError::InvalidBinary(format!(
-                    "record_size {} is too small, minimum is 46 bytes",
-                    record_size
+                    "unknown status byte: {}",
+                    other
                 )));
             }
+        };
 
-            // TX_ID
-            let mut buf8 = [0u8; 8];
-            r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
-            let tx_id = u64::from_be_bytes(buf8) as TxId;
-
-            // TX_TYPE
-            let mut buf1 = [0u8; 1];
-            r.read_exact(&mut buf1).map_err(BankFormatError::Io)?;
-            let tx_type = match buf1[0] {
-                0 => TxType::Deposit,
-                1 => TxType::Transfer,
-                2 => TxType::Withdrawal,
-                other => {
-                    return Err(BankFormatError::InvalidBinary(format!(
-                        "unknown tx_type byte: {}",
-                        other
-                    )));
-                }
+        // DESC_LEN
+        r.read_exact(&mut buf4).map_err(BankFormatError::Io)?;
+        let desc_len = endian.u32(buf4) as usize;
+        if desc_len > MAX_DESC_LEN {
+            return Err(BankFormatError::InvalidBinary(format!(
+                "description length {} exceeds maximum allowed {}",
+                desc_len, MAX_DESC_LEN
+            )));
+        }
+
+        // DESCRIPTION
+        let description = if desc_len > 0 {
+            let mut desc_buf = vec![0u8; desc_len];
+            r.read_exact(&mut desc_buf).map_err(BankFormatError::Io)?;
+            String::from_utf8(desc_buf)
+                .map_err(|e| BankFormatError::InvalidBinary(e.to_string()))?
+        } else {
+            String::new()
+        };
+
+        Ok(Transaction {
+            tx_id,
+            tx_type,
+            from_user_id,
+            to_user_id,
+            amount,
+            timestamp,
+            status,
+            description,
+        })
+    }
+}
+
+/// Write a human-readable dump of the binary records in `r` to `w`.
+///
+/// Every field is printed on its own line with its byte offset, its raw bytes in hex
+/// and its decoded value. The dump stops at the first corruption with a line starting
+/// with `!!` that gives the offset and the problem. This is a debugging aid; the
+/// output format is not stable. Only I/O errors are returned as errors.
+pub fn hexdump_records<R: Read>(r: &mut R, w: &mut impl Write) -> Result<(), BankFormatError> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let mut dump = HexDump {
+        bytes: &bytes,
+        pos: 0,
+        w,
+    };
+    let mut prev_id: TxId = 0;
+    let mut endian = Endian::Big;
+    let mut index = 0;
+
+    while dump.pos < bytes.len() {
+        let start = dump.pos;
+        if bytes[start..].starts_with(&FILE_MAGIC) {
+            writeln!(dump.w, "file header @ {start:#010x}")?;
+            let magic = dump.take(4).unwrap();
+            dump.line(start, magic, "magic", &String::from_utf8_lossy(magic))?;
+            let Some(version) = dump.field(1, "version", |b| b[0])? else {
+                return Ok(());
             };
+            let Some(flag) = dump.field(1, "endianness", |b| b[0])? else {
+                return Ok(());
+            };
+            match parse_file_header([version, flag]) {
+                Ok(e) => endian = e,
+                Err(BankFormatError::InvalidBinary(msg)) => return dump.corrupt(start + 4, &msg),
+                Err(e) => return Err(e),
+            }
+            continue;
+        }
+        if bytes[start..].starts_with(&FOOTER_MAGIC) {
+            let Some(raw) = dump.take(FOOTER_LEN) else {
+                return dump.corrupt(start, "truncated footer");
+            };
+            let footer = <&[u8; FOOTER_LEN]>::try_from(raw).unwrap();
+            return match BinFooter::from_bytes(footer) {
+                Some(footer) => dump.line(start, &raw[..4], "footer", &format!("{footer:?}")),
+                None => dump.corrupt(start, "malformed footer"),
+            };
+        }
 
-            // FROM_USER_ID
-            r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
-            let from_user_id = u64::from_be_bytes(buf8) as i64;
-
-            // TO_USER_ID
-            r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
-            let to_user_id = u64::from_be_bytes(buf8) as i64;
-
-            // AMOUNT
-            r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
-            let amount = i64::from_be_bytes(buf8);
-
-            // TIMESTAMP
-            r.read_exact(&mut buf8).map_err(BankFormatError::Io)?;
-            let timestamp = u64::from_be_bytes(buf8) as i64;
-
-            // STATUS
-            r.read_exact(&mut buf1).map_err(BankFormatError::Io)?;
-            let status = match buf1[0] {
-                0 => Status::Success,
-                1 => Status::Failure,
-                2 => Status::Pending,
-                other => {
-                    return Err(BankFormatError::InvalidBinary(format!(
-                        "unknown status byte: {}",
-                        other
-                    )));
+        writeln!(dump.w, "record {index} @ {start:#010x}")?;
+        let Some(magic) = dump.take(4) else {
+            return dump.corrupt(start, "truncated magic");
+        };
+        let kind = match check_magic(magic) {
+            Ok(kind) => kind,
+            Err(e) => return dump.corrupt(start, &e.to_string()),
+        };
+        dump.line(start, magic, "magic", &String::from_utf8_lossy(magic))?;
+
+        let Some(size) = dump.field(4, "record_size", |b| endian.u32(b.try_into().unwrap()))?
+        else {
+            return Ok(());
+        };
+        if size < kind.min_record_size() {
+            return dump.corrupt(
+                start + 4,
+                &format!(
+                    "record_size {size} is too small, minimum is {}",
+                    kind.min_record_size()
+                ),
+            );
+        }
+
+        let id_at = dump.pos;
+        let tx_id = match kind {
+            RecordKind::Plain => dump.field(8, "tx_id", |b| endian.u64(b.try_into().unwrap()))?,
+            RecordKind::Delta => {
+                let mut rest = &bytes[id_at..];
+                match read_varint(&mut rest) {
+                    Ok(zigzag) => {
+                        let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+                        let id = prev_id.wrapping_add_signed(delta);
+                        let raw = dump.take(bytes.len() - id_at - rest.len()).unwrap();
+                        dump.line(id_at, raw, "tx_id", &format!("{id} (delta {delta})"))?;
+                        Some(id)
+                    }
+                    Err(e) => return dump.corrupt(id_at, &format!("tx_id: {e}")),
                 }
-            };
+            }
+        };
+        let Some(tx_id) = tx_id else {
+            return Ok(());
+        };
+        prev_id = tx_id;
 
-            // DESC_LEN
-            r.read_exact(&mut buf4).map_err(BankFormatError::Io)?;
-            let desc_len = u32::from_be_bytes(buf4) as usize;
-            if desc_len > MAX_DESC_LEN {
-                return Err(BankFormatError::InvalidBinary(format!(
-                    "description length {} exceeds maximum allowed {}",
-                    desc_len, MAX_DESC_LEN
-                )));
+        let Some(tx_type) = dump.field(1, "tx_type", |b| b[0])? else {
+            return Ok(());
+        };
+        if tx_type > 2 {
+            return dump.corrupt(dump.pos - 1, &format!("unknown tx_type byte: {tx_type}"));
+        }
+        for name in ["from_user_id", "to_user_id", "amount", "timestamp"] {
+            if dump
+                .field(8, name, |b| endian.u64(b.try_into().unwrap()) as i64)?
+                .is_none()
+            {
+                return Ok(());
             }
+        }
+        let Some(status) = dump.field(1, "status", |b| b[0])? else {
+            return Ok(());
+        };
+        if status > 2 {
+            return dump.corrupt(dump.pos - 1, &format!("unknown status byte: {status}"));
+        }
+        let Some(desc_len) = dump.field(4, "desc_len", |b| endian.u32(b.try_into().unwrap()))?
+        else {
+            return Ok(());
+        };
+        if desc_len as usize > MAX_DESC_LEN {
+            return dump.corrupt(
+                dump.pos - 4,
+                &format!("description length {desc_len} exceeds maximum allowed {MAX_DESC_LEN}"),
+            );
+        }
+        let desc_at = dump.pos;
+        let Some(desc) = dump.take(desc_len as usize) else {
+            return dump.corrupt(desc_at, "truncated description");
+        };
+        match std::str::from_utf8(desc) {
+            Ok(desc) => dump.line(
+                desc_at,
+                desc.as_bytes(),
+                "description",
+                &format!("{desc:?}"),
+            )?,
+            Err(e) => return dump.corrupt(desc_at, &format!("description: {e}")),
+        }
 
-            // DESCRIPTION
-            let description = if desc_len > 0 {
-                let mut desc_buf = vec![0u8; desc_len];
-                r.read_exact(&mut desc_buf).map_err(BankFormatError::Io)?;
-                String::from_utf8(desc_buf)
-                    .map_err(|e| BankFormatError::InvalidBinary(e.to_string()))?
-            } else {
-                String::new()
-            };
+        let consumed = dump.pos - start - HEADER_LEN;
+        if consumed != size as usize {
+            return dump.corrupt(
+                start + 4,
+                &format!("record_size {size} does not match record contents ({consumed} bytes)"),
+            );
+        }
+        index += 1;
+    }
+    Ok(())
+}
 
-            transactions.push(Transaction {
-                tx_id,
-                tx_type,
-                from_user_id,
-                to_user_id,
-                amount,
-                timestamp,
-                status,
-                description,
-            });
+/// Cursor and output of [`hexdump_records`].
+struct HexDump<'a, W> {
+    bytes: &'a [u8],
+    pos: usize,
+    w: &'a mut W,
+}
+
+impl<'a, W: Write> HexDump<'a, W> {
+    /// Consume the next `n` bytes, or `None` if fewer are left.
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes: &'a [u8] = self.bytes;
+        let raw = bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(raw)
+    }
+
+    /// Consume and print a fixed-size field. A truncated field is reported as
+    /// corruption and yields `None`.
+    fn field<T: std::fmt::Display>(
+        &mut self,
+        n: usize,
+        name: &str,
+        decode: impl FnOnce(&[u8]) -> T,
+    ) -> Result<Option<T>, BankFormatError> {
+        let at = self.pos;
+        let Some(raw) = self.take(n) else {
+            self.corrupt(
+                at,
+                &format!(
+                    "truncated {name}: need {n} bytes, {} left",
+                    self.bytes.len() - at
+                ),
+            )?;
+            return Ok(None);
+        };
+        let value = decode(raw);
+        self.line(at, raw, name, &value.to_string())?;
+        Ok(Some(value))
+    }
+
+    fn line(
+        &mut self,
+        at: usize,
+        raw: &[u8],
+        name: &str,
+        value: &str,
+    ) -> Result<(), BankFormatError> {
+        const MAX_HEX_BYTES: usize = 8;
+        let mut hex: Vec<String> = raw
+            .iter()
+            .take(MAX_HEX_BYTES)
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        if raw.len() > MAX_HEX_BYTES {
+            hex.push("..".to_string());
         }
+        writeln!(
+            self.w,
+            "  {at:#010x}  {:<26}  {name:<12}  {value}",
+            hex.join(" ")
+        )?;
+        Ok(())
+    }
 
-        Ok(transactions)
+    fn corrupt(&mut self, at: usize, problem: &str) -> Result<(), BankFormatError> {
+        writeln!(self.w, "!! {at:#010x}  {problem}")?;
+        Ok(())
     }
+}
 
-    fn write_all<W: Write>(w: &mut W, records: &[Transaction]) -> Result<(), BankFormatError> {
-        for tx in records {
-            let desc_bytes = tx.description.as_bytes();
-            let desc_len = desc_bytes.len() as u32;
+/// Iterator over the records of a binary stream, returned by [`BinFormat::read_iter_with`].
+///
+/// Each record is read from the underlying reader only when requested. An error is
+/// yielded in place of the record that caused it and ends the iteration. Malformed
+/// records are reported with their 1-based ordinal and starting byte offset.
+#[derive(Debug)]
+pub struct BinReader<R> {
+    r: CountingReader<R>,
+    opts: BinOptions,
+    endian: Endian,
+    prev_id: TxId,
+    /// Number of records started so far.
+    records: usize,
+    done: bool,
+}
 
-            // body size: 8 + 1 + 8 + 8 + 8 + 8 + 1 + 4 + desc_len
-            let record_size: u32 = 8 + 1 + 8 + 8 + 8 + 8 + 1 + 4 + desc_len;
+impl<R: Read> BinReader<R> {
+    fn next_record(&mut self) -> Result<Option<Transaction>, BankFormatError> {
+        let mut magic = [0u8; 4];
+        let mut n = read_full(&mut self.r, &mut magic)?;
+        while n == magic.len() && magic == FILE_MAGIC {
+            self.endian = read_file_header(&mut self.r)?;
+            n = read_full(&mut self.r, &mut magic)?;
+        }
+        if n == 0 {
+            return Ok(None);
+        }
+        if magic == FOOTER_MAGIC {
+            read_footer_rest(&mut self.r)?;
+            if read_full(&mut self.r, &mut magic)? > 0
+                && self.opts.trailing == TrailingPolicy::Reject
+            {
+                return Err(BankFormatError::InvalidBinary(
+                    "unexpected data after footer".into(),
+                ));
+            }
+            return Ok(None);
+        }
+        self.records += 1;
+        let start = self.r.bytes_read() - n as u64;
+        let at = |e| match e {
+            BankFormatError::InvalidBinary(msg) => BankFormatError::InvalidBinary(format!(
+                "record {} at byte {}: {}",
+                self.records, start, msg
+            )),
+            e => e,
+        };
+        let kind = match check_magic(&magic[..n]) {
+            Ok(kind) => kind,
+            Err(e) => match self.opts.trailing {
+                TrailingPolicy::Reject => return Err(at(e)),
+                TrailingPolicy::Ignore => return Ok(None),
+            },
+        };
+        let tx = BinFormat::read_body(&mut self.r, &self.opts, self.endian, kind, self.prev_id)
+            .map_err(at)?;
+        self.prev_id = tx.tx_id;
+        Ok(Some(tx))
+    }
+}
 
-            // magic
-            w.write_all(&MAGIC).map_err(BankFormatError::Io)?;
+impl<R: Read> Iterator for BinReader<R> {
+    type Item = Result<Transaction, BankFormatError>;
 
-            // record size
-            w.write_all(&record_size.to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_record().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
 
-            // TX_ID
-            w.write_all(&(tx.tx_id as TxId).to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+/// Consume the rest of a footer after its magic, checking that it is well-formed.
+fn read_footer_rest<R: Read>(r: &mut R) -> Result<BinFooter, BankFormatError> {
+    let mut buf = [0u8; FOOTER_LEN];
+    buf[..4].copy_from_slice(&FOOTER_MAGIC);
+    r.read_exact(&mut buf[4..])?;
+    BinFooter::from_bytes(&buf)
+        .ok_or_else(|| BankFormatError::InvalidBinary("malformed footer".into()))
+}
 
-            // TX_TYPE
-            let tx_type_byte: u8 = match tx.tx_type {
-                TxType::Deposit => 0,
-                TxType::Transfer => 1,
-                TxType::Withdrawal => 2,
-            };
-            w.write_all(&[tx_type_byte]).map_err(BankFormatError::Io)?;
+/// Consume the rest of a file header after its magic and return its byte order.
+fn read_file_header<R: Read>(r: &mut R) -> Result<Endian, BankFormatError> {
+    let mut rest = [0u8; FILE_HEADER_LEN - 4];
+    r.read_exact(&mut rest)?;
+    parse_file_header(rest)
+}
 
-            // FROM_USER_ID
-            w.write_all(&(tx.from_user_id as u64).to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+/// Check the version and endianness flag of a file header.
+fn parse_file_header(
+    [version, flag]: [u8; FILE_HEADER_LEN - 4],
+) -> Result<Endian, BankFormatError> {
+    if version != FORMAT_VERSION {
+        return Err(BankFormatError::InvalidBinary(format!(
+            "unsupported version {version}"
+        )));
+    }
+    Endian::from_flag(flag)
+}
 
-            // TO_USER_ID
-            w.write_all(&(tx.to_user_id as u64).to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+/// Decode one complete record (magic included), requiring its size to match its contents.
+fn decode_record(
+    bytes: &[u8],
+    endian: Endian,
+    prev_id: TxId,
+) -> Result<Transaction, BankFormatError> {
+    let kind = check_magic(&bytes[..MAGIC.len()])?;
+    let mut record = &bytes[MAGIC.len()..];
+    let tx = BinFormat::read_body(&mut record, &BinOptions::default(), endian, kind, prev_id)?;
+    if !record.is_empty() {
+        return Err(BankFormatError::InvalidBinary(format!(
+            "record_size {} does not match record contents",
+            bytes.len() - HEADER_LEN
+        )));
+    }
+    Ok(tx)
+}
 
-            // AMOUNT
-            w.write_all(&tx.amount.to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+/// Encode an unknown enum value read with lenient enums back into its byte code.
+fn other_code(name: &str, field: &str) -> Result<u8, BankFormatError> {
+    name.parse::<u8>()
+        .ok()
+        .filter(|code| *code > 2)
+        .ok_or_else(|| {
+            BankFormatError::InvalidBinary(format!("{field} {name} has no binary encoding"))
+        })
+}
 
-            // TIMESTAMP
-            w.write_all(&(tx.timestamp as u64).to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+fn check_magic(magic: &[u8]) -> Result<RecordKind, BankFormatError> {
+    if magic.len() < MAGIC.len() {
+        return Err(BankFormatError::InvalidBinary(format!(
+            "truncated magic: {:?}",
+            magic
+        )));
+    }
+    if magic == MAGIC {
+        Ok(RecordKind::Plain)
+    } else if magic == DELTA_MAGIC {
+        Ok(RecordKind::Delta)
+    } else {
+        Err(BankFormatError::InvalidBinary(format!(
+            "invalid magic: {:?}",
+            magic
+        )))
+    }
+}
 
-            // STATUS
-            let status_byte: u8 = match tx.status {
-                Status::Success => 0,
-                Status::Failure => 1,
-                Status::Pending => 2,
-            };
-            w.write_all(&[status_byte]).map_err(BankFormatError::Io)?;
+/// Write the file header declaring the format version and byte order.
+pub(crate) fn write_file_header<W: Write>(
+    w: &mut W,
+    endian: Endian,
+) -> Result<(), BankFormatError> {
+    let mut header = [0u8; FILE_HEADER_LEN];
+    header[..4].copy_from_slice(&FILE_MAGIC);
+    header[4] = FORMAT_VERSION;
+    header[5] = endian.flag();
+    w.write_all(&header).map_err(BankFormatError::Io)
+}
 
-            // DESC_LEN
-            w.write_all(&desc_len.to_be_bytes())
-                .map_err(BankFormatError::Io)?;
+/// Serialize one record into `buf`, replacing its contents.
+///
+/// `prev_id` is the id of the previously encoded record, used and updated when
+/// [`BinOptions::delta_ids`] is set.
+pub(crate) fn encode_record(
+    buf: &mut Vec<u8>,
+    tx: &Transaction,
+    opts: &BinOptions,
+    prev_id: &mut TxId,
+) -> Result<(), BankFormatError> {
+    let endian = opts.endian;
+    let desc_bytes = tx.description.as_bytes();
+    buf.clear();
 
-            // DESCRIPTION
-            if desc_len > 0 {
-                w.write_all(desc_bytes).map_err(BankFormatError::Io)?;
-            }
+    // magic, then a record size placeholder filled in once the body is known
+    let magic = if opts.delta_ids { DELTA_MAGIC } else { MAGIC };
+    buf.extend_from_slice(&magic);
+    buf.extend_from_slice(&[0u8; 4]);
+
+    // TX_ID
+    if opts.delta_ids {
+        let delta = tx.tx_id.wrapping_sub(*prev_id) as i64;
+        write_varint(buf, ((delta << 1) ^ (delta >> 63)) as u64);
+        *prev_id = tx.tx_id;
+    } else {
+        buf.extend_from_slice(&endian.u64_bytes(tx.tx_id));
+    }
+
+    // TX_TYPE
+    let tx_type_byte: u8 = match &tx.tx_type {
+        TxType::Deposit => 0,
+        TxType::Transfer => 1,
+        TxType::Withdrawal => 2,
+        TxType::Other(name) => other_code(name, "tx_type")?,
+    };
+    buf.push(tx_type_byte);
+
+    // FROM_USER_ID
+    buf.extend_from_slice(&endian.u64_bytes(tx.from_user_id as u64));
+
+    // TO_USER_ID
+    buf.extend_from_slice(&endian.u64_bytes(tx.to_user_id as u64));
+
+    // AMOUNT
+    buf.extend_from_slice(&endian.u64_bytes(tx.amount as u64));
+
+    // TIMESTAMP
+    buf.extend_from_slice(&endian.u64_bytes(tx.timestamp as u64));
+
+    // STATUS
+    let status_byte: u8 = match &tx.status {
+        Status::Success => 0,
+        Status::Failure => 1,
+        Status::Pending => 2,
+        Status::Other(name) => other_code(name, "status")?,
+    };
+    buf.push(status_byte);
+
+    // DESC_LEN
+    buf.extend_from_slice(&endian.u32_bytes(desc_bytes.len() as u32));
+
+    // DESCRIPTION
+    buf.extend_from_slice(desc_bytes);
+
+    // record size
+    let record_size = (buf.len() - HEADER_LEN) as u32;
+    buf[4..HEADER_LEN].copy_from_slice(&endian.u32_bytes(record_size));
+    Ok(())
+}
+
+/// Append `value` as an unsigned LEB128 varint.
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Read an unsigned LEB128 varint of at most [`MAX_VARINT_LEN`] bytes.
+fn read_varint<R: Read>(r: &mut R) -> Result<u64, BankFormatError> {
+    let mut value = 0u64;
+    let mut byte = [0u8; 1];
+    for i in 0..MAX_VARINT_LEN {
+        r.read_exact(&mut byte).map_err(BankFormatError::Io)?;
+        value |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
         }
+    }
+    Err(BankFormatError::InvalidBinary(
+        "tx_id delta varint is too long".into(),
+    ))
+}
 
-        Ok(())
+/// Fill `buf` from `r`, returning the number of bytes read.
+/// Fewer than `buf.len()` bytes are returned only at EOF.
+fn read_full<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<usize, BankFormatError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(BankFormatError::Io(e)),
+        }
     }
+    Ok(filled)
 }
 
 #[cfg(test)]
@@ -205,10 +1071,11 @@ mod tests {
         }
     }
 
+    /// A single record without a file header, as in files from before the header.
     fn make_valid_record() -> Vec<u8> {
         let mut buf = Vec::new();
         BinFormat::write_all(&mut buf, &[valid_transaction()]).unwrap();
-        buf
+        buf.split_off(FILE_HEADER_LEN)
     }
 
     #[test]
@@ -285,4 +1152,375 @@ mod tests {
         assert_eq!(original, parsed);
         Ok(())
     }
+
+    #[test]
+    fn test_follow_picks_up_appended_records() {
+        use std::sync::Arc;
+        use std::sync::mpsc;
+
+        let path = std::env::temp_dir().join(format!("ypbank_follow_{}.ypbn", std::process::id()));
+        let mut second = valid_transaction();
+        second.tx_id = 2;
+        let mut second_bytes = Vec::new();
+        BinFormat::write_all(&mut second_bytes, &[second.clone()]).unwrap();
+
+        let mut writer = std::fs::File::create(&path).unwrap();
+        writer.write_all(&make_valid_record()).unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let follower = {
+            let stop = Arc::clone(&stop);
+            let mut reader = std::fs::File::open(&path).unwrap();
+            std::thread::spawn(move || {
+                BinFormat::follow(&mut reader, &stop, |tx| sender.send(tx).unwrap())
+            })
+        };
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), valid_transaction());
+
+        // a partially written record must not be yielded
+        let (head, tail) = second_bytes.split_at(20);
+        writer.write_all(head).unwrap();
+        std::thread::sleep(FOLLOW_POLL_INTERVAL * 4);
+        assert!(receiver.try_recv().is_err());
+
+        writer.write_all(tail).unwrap();
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), second);
+
+        stop.store(true, Ordering::Relaxed);
+        follower.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trailing_garbage_policies() {
+        let reject = BinOptions::default();
+        let ignore = BinOptions {
+            trailing: TrailingPolicy::Ignore,
+            ..BinOptions::default()
+        };
+
+        for garbage in [&b"garbage!"[..], &b"YP"[..]] {
+            let mut buf = make_valid_record();
+            buf.extend_from_slice(garbage);
+
+            match BinFormat::read_all_with(&mut Cursor::new(&buf), &reject) {
+                Err(BankFormatError::InvalidBinary(msg)) => {
+                    assert!(msg.contains("magic"), "got: {}", msg)
+                }
+                other => panic!("expected InvalidBinary, got {:?}", other),
+            }
+
+            let parsed = BinFormat::read_all_with(&mut Cursor::new(&buf), &ignore).unwrap();
+            assert_eq!(parsed, vec![valid_transaction()]);
+        }
+    }
+
+    #[test]
+    fn test_lenient_enums_preserve_unknown_codes() {
+        let mut buf = make_valid_record();
+        buf[16] = 7; // tx_type
+        buf[49] = 9; // status
+        let opts = BinOptions {
+            lenient_enums: true,
+            ..BinOptions::default()
+        };
+
+        let parsed = BinFormat::read_all_with(&mut Cursor::new(&buf), &opts).unwrap();
+        assert_eq!(parsed[0].tx_type, TxType::Other("7".into()));
+        assert_eq!(parsed[0].status, Status::Other("9".into()));
+
+        let mut written = Vec::new();
+        BinFormat::write_all(&mut written, &parsed).unwrap();
+        assert_eq!(written[FILE_HEADER_LEN..], buf);
+
+        let mut unencodable = parsed[0].clone();
+        unencodable.tx_type = TxType::Other("REFUND".into());
+        assert!(matches!(
+            BinFormat::write_all(&mut Vec::new(), &[unencodable]),
+            Err(BankFormatError::InvalidBinary(msg)) if msg.contains("tx_type REFUND")
+        ));
+    }
+
+    #[test]
+    fn test_delta_ids_roundtrip_and_size() {
+        let sequential: Vec<Transaction> = (1000..1100)
+            .map(|tx_id| Transaction {
+                tx_id,
+                ..valid_transaction()
+            })
+            .collect();
+        let delta_opts = BinOptions {
+            delta_ids: true,
+            ..BinOptions::default()
+        };
+
+        let mut plain = Vec::new();
+        BinFormat::write_all(&mut plain, &sequential).unwrap();
+        let mut delta = Vec::new();
+        BinFormat::write_all_with(&mut delta, &sequential, &delta_opts).unwrap();
+        assert!(delta.len() < plain.len());
+        assert_eq!(&delta[FILE_HEADER_LEN..][..4], b"YPBD");
+        assert_eq!(
+            BinFormat::read_all(&mut delta.as_slice()).unwrap(),
+            sequential
+        );
+
+        // Non-monotonic ids, including wrap-around distances, resolve exactly.
+        let mixed: Vec<Transaction> = [5, 3, u64::MAX, 0, 1 << 40]
+            .into_iter()
+            .map(|tx_id| Transaction {
+                tx_id,
+                ..valid_transaction()
+            })
+            .collect();
+        let mut delta = Vec::new();
+        BinFormat::write_all_with(&mut delta, &mixed, &delta_opts).unwrap();
+        assert_eq!(BinFormat::read_all(&mut delta.as_slice()).unwrap(), mixed);
+    }
+
+    #[test]
+    fn test_write_all_byte_layout() {
+        struct CountingWriter(Vec<u8>, usize);
+        impl Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.1 += 1;
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"YPBN");
+        expected.extend_from_slice(&50u32.to_be_bytes());
+        expected.extend_from_slice(&1u64.to_be_bytes());
+        expected.push(0);
+        expected.extend_from_slice(&0u64.to_be_bytes());
+        expected.extend_from_slice(&42u64.to_be_bytes());
+        expected.extend_from_slice(&1000i64.to_be_bytes());
+        expected.extend_from_slice(&1234567890u64.to_be_bytes());
+        expected.push(0);
+        expected.extend_from_slice(&4u32.to_be_bytes());
+        expected.extend_from_slice(b"test");
+
+        let mut w = CountingWriter(Vec::new(), 0);
+        BinFormat::write_all(&mut w, &[valid_transaction(), valid_transaction()]).unwrap();
+        assert_eq!(
+            w.0,
+            [b"YPBH\x01\x00".to_vec(), expected.clone(), expected].concat()
+        );
+        assert_eq!(w.1, 3);
+    }
+
+    #[test]
+    fn test_read_all_raw_reproduces_input() {
+        let second = Transaction {
+            tx_id: 2,
+            description: "second".to_string(),
+            ..valid_transaction()
+        };
+        let opts = BinOptions {
+            delta_ids: true,
+            ..BinOptions::default()
+        };
+        let mut input = Vec::new();
+        BinFormat::write_all_with(&mut input, &[valid_transaction(), second.clone()], &opts)
+            .unwrap();
+
+        let raw = BinFormat::read_all_raw(&mut input.as_slice()).unwrap();
+        let txs: Vec<Transaction> = raw.records.iter().map(|(tx, _)| tx.clone()).collect();
+        assert_eq!(txs, vec![valid_transaction(), second.clone()]);
+        assert!(raw.trailing.is_empty());
+        assert_eq!(raw.into_bytes(), input);
+
+        let mut with_footer = Vec::new();
+        BinFormat::write_all_with_footer(&mut with_footer, &[valid_transaction(), second]).unwrap();
+        let raw = BinFormat::read_all_raw(&mut with_footer.as_slice()).unwrap();
+        assert_eq!(raw.records.len(), 2);
+        assert_eq!(raw.trailing.len(), FOOTER_LEN);
+        assert_eq!(raw.into_bytes(), with_footer);
+
+        let mut empty = Vec::new();
+        BinFormat::write_all(&mut empty, &[]).unwrap();
+        let raw = BinFormat::read_all_raw(&mut empty.as_slice()).unwrap();
+        assert!(raw.records.is_empty());
+        assert_eq!(raw.trailing.len(), FILE_HEADER_LEN);
+        assert_eq!(raw.into_bytes(), empty);
+
+        let mut empty = Vec::new();
+        BinFormat::write_all_with_footer(&mut empty, &[]).unwrap();
+        let raw = BinFormat::read_all_raw(&mut empty.as_slice()).unwrap();
+        assert_eq!(raw.into_bytes(), empty);
+
+        let truncated = &input[..input.len() - 1];
+        assert!(BinFormat::read_all_raw(&mut &truncated[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_one_record_at_a_time() {
+        let second = Transaction {
+            tx_id: 2,
+            ..valid_transaction()
+        };
+        let mut buf = Vec::new();
+        BinFormat::write_all(&mut buf, &[valid_transaction(), second.clone()]).unwrap();
+
+        let mut r = buf.as_slice();
+        assert_eq!(
+            BinFormat::read_one(&mut r).unwrap(),
+            Some(valid_transaction())
+        );
+        assert_eq!(BinFormat::read_one(&mut r).unwrap(), Some(second));
+        assert_eq!(BinFormat::read_one(&mut r).unwrap(), None);
+
+        let truncated = &buf[..10];
+        assert!(BinFormat::read_one(&mut &truncated[..]).is_err());
+    }
+
+    #[test]
+    fn test_footer_roundtrip() {
+        let records = vec![
+            Transaction {
+                tx_id: 1,
+                timestamp: 300,
+                ..valid_transaction()
+            },
+            Transaction {
+                tx_id: 2,
+                timestamp: 100,
+                amount: 250,
+                ..valid_transaction()
+            },
+            Transaction {
+                tx_id: 3,
+                timestamp: 200,
+                status: Status::Failure,
+                ..valid_transaction()
+            },
+        ];
+        let mut buf = Vec::new();
+        BinFormat::write_all_with_footer(&mut buf, &records).unwrap();
+
+        let footer = BinFormat::read_footer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(
+            footer,
+            Some(BinFooter {
+                count: 3,
+                min_timestamp: 100,
+                max_timestamp: 300,
+                total_success_amount: 1250,
+            })
+        );
+        assert_eq!(BinFormat::read_all(&mut buf.as_slice()).unwrap(), records);
+
+        let plain = make_valid_record();
+        assert_eq!(
+            BinFormat::read_footer(&mut Cursor::new(&plain)).unwrap(),
+            None
+        );
+
+        let huge = Transaction {
+            amount: i64::MAX,
+            ..valid_transaction()
+        };
+        let mut out = Vec::new();
+        assert!(matches!(
+            BinFormat::write_all_with_footer(&mut out, &[huge.clone(), huge]),
+            Err(BankFormatError::InvalidBinary(_))
+        ));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_hexdump_records() {
+        let mut out = Vec::new();
+        hexdump_records(&mut make_valid_record().as_slice(), &mut out).unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        assert!(dump.starts_with("record 0 @ 0x00000000\n"));
+        assert!(dump.contains("  0x00000000  59 50 42 4e                 magic         YPBN\n"));
+        assert!(dump.contains("  0x00000008  00 00 00 00 00 00 00 01     tx_id         1\n"));
+        assert!(
+            dump.contains("  0x00000036  74 65 73 74                 description   \"test\"\n")
+        );
+        assert!(!dump.contains("!!"));
+
+        let mut corrupt = make_valid_record();
+        corrupt[49] = 0x07; // status
+        corrupt.extend_from_slice(&make_valid_record());
+        let mut out = Vec::new();
+        hexdump_records(&mut corrupt.as_slice(), &mut out).unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        assert!(dump.ends_with("!! 0x00000031  unknown status byte: 7\n"));
+        assert!(!dump.contains("record 1"));
+
+        let truncated = &make_valid_record()[..20];
+        let mut out = Vec::new();
+        hexdump_records(&mut &truncated[..], &mut out).unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        assert!(dump.ends_with("!! 0x00000011  truncated from_user_id: need 8 bytes, 3 left\n"));
+    }
+
+    #[test]
+    fn test_read_iter_yields_error_mid_stream() {
+        let mut data = make_valid_record();
+        data.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+
+        let mut iter = BinFormat::read_iter(data.as_slice());
+        assert_eq!(iter.next().unwrap().unwrap(), valid_transaction());
+        match iter.next() {
+            Some(Err(BankFormatError::InvalidBinary(msg))) => {
+                let prefix = format!("record 2 at byte {}: ", make_valid_record().len());
+                assert!(msg.starts_with(&prefix), "got {msg}");
+                assert!(msg.contains("invalid magic"), "got {msg}");
+            }
+            other => panic!("expected InvalidBinary, got {other:?}"),
+        }
+        assert!(iter.next().is_none());
+
+        let ids: Vec<TxId> = BinFormat::read_iter(make_valid_record().as_slice())
+            .map(|tx| tx.unwrap().tx_id)
+            .collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn test_file_header_version_and_endianness() {
+        let records = vec![valid_transaction(), valid_transaction()];
+        let little = BinOptions {
+            endian: Endian::Little,
+            ..BinOptions::default()
+        };
+        let mut buf = Vec::new();
+        BinFormat::write_all_with(&mut buf, &records, &little).unwrap();
+        assert_eq!(&buf[..FILE_HEADER_LEN], b"YPBH\x01\x01");
+        assert_eq!(&buf[FILE_HEADER_LEN + 8..][..8], &1u64.to_le_bytes());
+        assert_eq!(BinFormat::read_all(&mut buf.as_slice()).unwrap(), records);
+        let raw = BinFormat::read_all_raw(&mut buf.as_slice()).unwrap();
+        assert_eq!(raw.into_bytes(), buf);
+        assert!(BinFormat::read_one(&mut buf.as_slice()).is_err());
+
+        let mut dump = Vec::new();
+        hexdump_records(&mut buf.as_slice(), &mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.starts_with("file header @ 0x00000000\n"));
+        assert!(dump.contains("  0x0000000e  01 00 00 00 00 00 00 00     tx_id         1\n"));
+
+        // a headerless stream from before the file header is read as big-endian
+        let legacy = [make_valid_record(), make_valid_record()].concat();
+        assert_eq!(
+            BinFormat::read_all(&mut legacy.as_slice()).unwrap(),
+            records
+        );
+
+        let mut future = buf.clone();
+        future[4] = 2;
+        match BinFormat::read_all(&mut future.as_slice()) {
+            Err(BankFormatError::InvalidBinary(msg)) => assert_eq!(msg, "unsupported version 2"),
+            other => panic!("expected InvalidBinary, got {other:?}"),
+        }
+    }
 }
@@ -0,0 +1,152 @@
+//! Transparent gzip and zstd (de)compression of transaction streams.
+//!
+//! Available with the `compression` feature.
+use crate::error::BankFormatError;
+use std::io::{Cursor, Read, Write};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A compression codec.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Compression {
+    /// Uncompressed data.
+    #[default]
+    None,
+    /// gzip (RFC 1952).
+    Gzip,
+    /// Zstandard.
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the codec from the leading bytes of a stream.
+    pub fn detect(prefix: &[u8]) -> Compression {
+        if prefix.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if prefix.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wrap `r` in a decompressor chosen by sniffing its magic bytes.
+///
+/// Input that is neither gzip nor zstd is passed through unchanged.
+pub fn decompress_auto<'a, R: Read + 'a>(
+    mut r: R,
+) -> Result<(Compression, Box<dyn Read + 'a>), BankFormatError> {
+    let mut prefix = [0u8; 4];
+    let mut filled = 0;
+    while filled < prefix.len() {
+        match r.read(&mut prefix[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(BankFormatError::Io(e)),
+        }
+    }
+
+    let compression = Compression::detect(&prefix[..filled]);
+    let stream = Cursor::new(prefix[..filled].to_vec()).chain(r);
+    let reader: Box<dyn Read + 'a> = match compression {
+        Compression::None => Box::new(stream),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(stream)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(stream)?),
+    };
+    Ok((compression, reader))
+}
+
+/// A writer that compresses its output with the selected [`Compression`].
+///
+/// [`CompressWriter::finish`] must be called to write the trailing frame data.
+pub enum CompressWriter<W: Write> {
+    /// Pass-through writer.
+    None(W),
+    /// gzip encoder.
+    Gzip(flate2::write::GzEncoder<W>),
+    /// Zstandard encoder.
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressWriter<W> {
+    /// Wrap `w` with an encoder for `compression`, using the default level.
+    pub fn new(w: W, compression: Compression) -> Result<Self, BankFormatError> {
+        Ok(match compression {
+            Compression::None => CompressWriter::None(w),
+            Compression::Gzip => CompressWriter::Gzip(flate2::write::GzEncoder::new(
+                w,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => CompressWriter::Zstd(zstd::Encoder::new(w, 0)?),
+        })
+    }
+
+    /// Finish the compressed stream and return the inner writer.
+    pub fn finish(self) -> Result<W, BankFormatError> {
+        let mut w = match self {
+            CompressWriter::None(w) => w,
+            CompressWriter::Gzip(e) => e.finish()?,
+            CompressWriter::Zstd(e) => e.finish()?,
+        };
+        w.flush()?;
+        Ok(w)
+    }
+}
+
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressWriter::None(w) => w.write(buf),
+            CompressWriter::Gzip(e) => e.write(buf),
+            CompressWriter::Zstd(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressWriter::None(w) => w.flush(),
+            CompressWriter::Gzip(e) => e.flush(),
+            CompressWriter::Zstd(e) => e.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bin_format::BinFormat;
+    use crate::{BankFormat, CsvFormat, convert};
+
+    const CSV: &str = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                       1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                       2,TRANSFER,10,20,500,1234567891,PENDING,second\n";
+
+    #[test]
+    fn test_gzip_csv_to_zstd_binary() {
+        let mut gz = CompressWriter::new(Vec::new(), Compression::Gzip).unwrap();
+        gz.write_all(CSV.as_bytes()).unwrap();
+        let gz_csv = gz.finish().unwrap();
+
+        let (detected, mut input) = decompress_auto(gz_csv.as_slice()).unwrap();
+        assert_eq!(detected, Compression::Gzip);
+        let mut output = CompressWriter::new(Vec::new(), Compression::Zstd).unwrap();
+        convert::<CsvFormat, BinFormat>(&mut input, &mut output).unwrap();
+        let zstd_bin = output.finish().unwrap();
+        assert_eq!(Compression::detect(&zstd_bin), Compression::Zstd);
+
+        let (detected, mut decoded) = decompress_auto(zstd_bin.as_slice()).unwrap();
+        assert_eq!(detected, Compression::Zstd);
+        let expected = CsvFormat::read_all(&mut CSV.as_bytes()).unwrap();
+        assert_eq!(BinFormat::read_all(&mut decoded).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_uncompressed_passthrough() {
+        let (detected, mut input) = decompress_auto(CSV.as_bytes()).unwrap();
+        assert_eq!(detected, Compression::None);
+        assert_eq!(CsvFormat::read_all(&mut input).unwrap().len(), 2);
+    }
+}
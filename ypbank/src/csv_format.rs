@@ -1,97 +1,663 @@
-use crate::error::BankFormatError;
-use crate::{BankFormat, Status, Transaction, TxType};
+use crate::error::{BankFormatError, FieldError};
+use crate::{BankFormat, Field, Status, Transaction, TxType, collect_lenient};
+use std::collections::HashMap;
 
 pub struct CsvFormat;
 
+/// Options controlling how [`CsvFormat`] parses its input.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Field delimiter. Defaults to `,`.
+    pub delimiter: u8,
+    /// Quote character. Defaults to `"`.
+    pub quote: u8,
+    /// When set, `amount` is read as a decimal number of major units with up to this many
+    /// fraction digits, e.g. `10.00` with `Some(2)` becomes `1000`.
+    /// Defaults to `None`, meaning amounts are integer minor units.
+    pub decimal_places: Option<u32>,
+    /// Decimal separator used when `decimal_places` is set. Defaults to `.`.
+    pub decimal_separator: char,
+    /// How a decimal amount with more than `decimal_places` fraction digits is handled.
+    /// Defaults to [`RoundingMode::Reject`].
+    pub rounding: RoundingMode,
+    /// Base used to parse `tx_id`, the user ids, and integer `amount`s. Defaults to `10`.
+    /// With base 16 an optional `0x` prefix is accepted. Must lie in `2..=36`; any
+    /// other base makes reading fail with a parse error.
+    pub numeric_radix: u32,
+    /// Preserve unknown `tx_type`/`status` strings as [`TxType::Other`]/[`Status::Other`]
+    /// instead of failing. Defaults to `false`.
+    pub lenient_enums: bool,
+    /// Strip a leading currency symbol (`$`, `€`, `£`, `¥`) or a trailing ISO 4217 code
+    /// (`1000 USD`) from `amount` before parsing it. Defaults to `false`.
+    pub strip_currency: bool,
+    /// Read an empty `from_user_id`/`to_user_id` cell as the system id `0`.
+    /// Defaults to `false`. Pairs with [`CsvWriteOptions::system_id_as_blank`].
+    pub system_id_as_blank: bool,
+}
+
+/// Handling of decimal amounts that are more precise than the minor unit.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum RoundingMode {
+    /// Fail to parse the amount.
+    #[default]
+    Reject,
+    /// Round to the nearest minor unit, with ties away from zero (`10.005` becomes `1001`).
+    HalfUp,
+    /// Drop the excess digits (`10.005` becomes `1000`).
+    Truncate,
+}
+
+/// Options controlling how [`CsvFormat`] writes its output.
+#[derive(Debug, Clone)]
+pub struct CsvWriteOptions {
+    /// Quote character. Defaults to `"`. Pairs with [`CsvOptions::quote`].
+    pub quote: u8,
+    /// Write a `from_user_id`/`to_user_id` of `0` (the system) as an empty cell
+    /// instead of a literal `0`. Defaults to `false`.
+    pub system_id_as_blank: bool,
+    /// Append a `row_checksum` column holding the CRC-32 of each row's other cells,
+    /// in column order. Defaults to `false`. Readers validate the column whenever it
+    /// is present.
+    pub row_checksum: bool,
+    /// Write the amount in major units as an `amount_major` column.
+    /// Defaults to [`AmountMajor::Off`].
+    pub amount_major: AmountMajor,
+}
+
+/// Header name of the decimal amount column written for [`AmountMajor`].
+const AMOUNT_MAJOR: &str = "amount_major";
+
+/// Whether and where [`CsvFormat`] writes the amount as a decimal of major units.
+///
+/// The value holds the number of fraction digits, e.g. `1000` with `2` is written
+/// as `10.00`. Formatting is exact integer arithmetic. [`CsvFormat`] ignores the
+/// `amount_major` column when reading, so files written with [`AmountMajor::Instead`]
+/// can not be read back.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum AmountMajor {
+    /// Only write the integer `amount` column.
+    #[default]
+    Off,
+    /// Write `amount_major` right after `amount`.
+    Alongside(u32),
+    /// Write `amount_major` in place of `amount`.
+    Instead(u32),
+}
+
+impl Default for CsvWriteOptions {
+    fn default() -> Self {
+        CsvWriteOptions {
+            quote: b'"',
+            system_id_as_blank: false,
+            row_checksum: false,
+            amount_major: AmountMajor::Off,
+        }
+    }
+}
+
+/// Header name of the optional checksum column.
+const ROW_CHECKSUM: &str = "row_checksum";
+
+impl CsvWriteOptions {
+    /// Wrap `w` in a CSV writer and write the header row.
+    pub(crate) fn writer<W: std::io::Write>(
+        &self,
+        w: W,
+    ) -> Result<csv::Writer<W>, BankFormatError> {
+        let mut wtr = csv::WriterBuilder::new().quote(self.quote).from_writer(w);
+        let mut header: Vec<&str> = Field::ALL.iter().map(Field::as_str).collect();
+        match self.amount_major {
+            AmountMajor::Off => {}
+            AmountMajor::Alongside(_) => header.insert(5, AMOUNT_MAJOR),
+            AmountMajor::Instead(_) => header[4] = AMOUNT_MAJOR,
+        }
+        if self.row_checksum {
+            header.push(ROW_CHECKSUM);
+        }
+        wtr.write_record(&header).map_err(BankFormatError::Csv)?;
+        Ok(wtr)
+    }
+
+    /// Write the row of one transaction to a writer from [`CsvWriteOptions::writer`].
+    pub(crate) fn write_row<W: std::io::Write>(
+        &self,
+        wtr: &mut csv::Writer<W>,
+        tx: &Transaction,
+    ) -> Result<(), BankFormatError> {
+        let user_id = |id: i64| {
+            if self.system_id_as_blank && id == 0 {
+                String::new()
+            } else {
+                id.to_string()
+            }
+        };
+
+        let mut row = vec![
+            tx.tx_id.to_string(),
+            tx.tx_type.to_string(),
+            user_id(tx.from_user_id),
+            user_id(tx.to_user_id),
+            tx.amount.to_string(),
+            tx.timestamp.to_string(),
+            tx.status.to_string(),
+            tx.description.clone(),
+        ];
+        match self.amount_major {
+            AmountMajor::Off => {}
+            AmountMajor::Alongside(places) => row.insert(5, format_decimal(tx.amount, places)),
+            AmountMajor::Instead(places) => row[4] = format_decimal(tx.amount, places),
+        }
+        if self.row_checksum {
+            row.push(format!(
+                "{:08x}",
+                row_checksum(row.iter().map(String::as_str))
+            ));
+        }
+        wtr.write_record(&row).map_err(BankFormatError::Csv)
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            decimal_places: None,
+            decimal_separator: '.',
+            rounding: RoundingMode::Reject,
+            numeric_radix: 10,
+            lenient_enums: false,
+            strip_currency: false,
+            system_id_as_blank: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Reject a `numeric_radix` that integers can not be parsed in.
+    fn check_radix(&self) -> Result<(), BankFormatError> {
+        if (2..=36).contains(&self.numeric_radix) {
+            Ok(())
+        } else {
+            Err(BankFormatError::Parse(format!(
+                "unsupported numeric_radix {}",
+                self.numeric_radix
+            )))
+        }
+    }
+
+    fn parse_amount(&self, s: &str) -> Option<i64> {
+        let s = if self.strip_currency {
+            strip_currency(s)
+        } else {
+            s
+        };
+        match self.decimal_places {
+            Some(places) => parse_decimal(s, places, self.decimal_separator, self.rounding),
+            None => parse_i64(s, self.numeric_radix),
+        }
+    }
+
+    /// Parse the eight columns of one transaction, in [`Field::ALL`] order.
+    fn parse_transaction(&self, fields: &[&str]) -> Result<Transaction, FieldError> {
+        let invalid =
+            |i: usize| FieldError::invalid(Field::ALL[i], Field::ALL[i].as_str(), fields[i]);
+        Ok(Transaction {
+            tx_id: parse_u64(fields[0], self.numeric_radix).ok_or_else(|| invalid(0))?,
+            tx_type: TxType::from_name(fields[1], self.lenient_enums)
+                .map_err(|e| FieldError::from_error(Field::TxType, e))?,
+            from_user_id: self.parse_user_id(fields[2]).ok_or_else(|| invalid(2))?,
+            to_user_id: self.parse_user_id(fields[3]).ok_or_else(|| invalid(3))?,
+            amount: self.parse_amount(fields[4]).ok_or_else(|| invalid(4))?,
+            timestamp: fields[5].parse().map_err(|_| invalid(5))?,
+            status: Status::from_name(fields[6], self.lenient_enums)
+                .map_err(|e| FieldError::from_error(Field::Status, e))?,
+            description: fields[7].to_string(),
+        })
+    }
+
+    fn parse_user_id(&self, s: &str) -> Option<i64> {
+        if self.system_id_as_blank && s.is_empty() {
+            return Some(0);
+        }
+        parse_i64(s, self.numeric_radix)
+    }
+}
+
 impl BankFormat for CsvFormat {
-    fn read_all<R: std::io::Read>(r: &mut R) -> Result<Vec<Transaction>, BankFormatError> {
-        let mut rdr = csv::Reader::from_reader(r);
+    fn read_iter<R: std::io::Read>(
+        r: R,
+    ) -> impl Iterator<Item = Result<Transaction, BankFormatError>> {
+        CsvFormat::read_iter_with(r, CsvOptions::default())
+    }
+
+    fn read_all_lenient<R: std::io::Read>(
+        r: &mut R,
+    ) -> (Vec<Transaction>, Vec<(usize, BankFormatError)>) {
+        let mut reader = CsvFormat::read_iter_with(r, CsvOptions::default());
+        collect_lenient(|| reader.next_record())
+    }
+
+    fn write_all<W: std::io::Write>(
+        w: &mut W,
+        records: &[Transaction],
+    ) -> Result<(), BankFormatError> {
+        CsvFormat::write_all_with(w, records, &CsvWriteOptions::default())
+    }
+}
+
+impl CsvFormat {
+    /// Write all transactions using the given [`CsvWriteOptions`].
+    pub fn write_all_with<W: std::io::Write>(
+        w: &mut W,
+        records: &[Transaction],
+        opts: &CsvWriteOptions,
+    ) -> Result<(), BankFormatError> {
+        let mut wtr = opts.writer(w)?;
+        for tx in records {
+            opts.write_row(&mut wtr, tx)?;
+        }
+
+        wtr.flush().map_err(BankFormatError::Io)?;
+        Ok(())
+    }
+
+    /// Read all transactions using the given [`CsvOptions`].
+    pub fn read_all_with<R: std::io::Read>(
+        r: &mut R,
+        opts: &CsvOptions,
+    ) -> Result<Vec<Transaction>, BankFormatError> {
+        CsvFormat::read_iter_with(r, opts.clone()).collect()
+    }
+
+    /// Stream transactions one row at a time using the given [`CsvOptions`].
+    pub fn read_iter_with<R: std::io::Read>(r: R, opts: CsvOptions) -> CsvReader<R> {
+        let rdr = csv::ReaderBuilder::new()
+            .delimiter(opts.delimiter)
+            .quote(opts.quote)
+            .from_reader(r);
+        CsvReader {
+            rdr,
+            record: csv::StringRecord::new(),
+            invalid_options: opts.check_radix().err(),
+            opts,
+            columns: None,
+            done: false,
+        }
+    }
+}
+
+/// Iterator over the rows of a CSV stream, returned by [`CsvFormat::read_iter_with`].
+///
+/// The header is read with the first row and columns are looked up by name, so their
+/// order does not matter and unknown columns are ignored. An error is yielded in place
+/// of the row that caused it and ends the iteration.
+#[derive(Debug)]
+pub struct CsvReader<R> {
+    rdr: csv::Reader<R>,
+    record: csv::StringRecord,
+    opts: CsvOptions,
+    /// An error in `opts`, yielded instead of the first row.
+    invalid_options: Option<BankFormatError>,
+    /// Column positions, once the header is read.
+    columns: Option<Columns>,
+    done: bool,
+}
+
+/// Positions of the known columns in a CSV header.
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    /// Position of each field, in [`Field::ALL`] order.
+    fields: [usize; 8],
+    /// Position of the `row_checksum` column, if present.
+    checksum: Option<usize>,
+}
+
+impl Columns {
+    /// Map every required column to its position. The first of repeated names wins.
+    fn from_headers(headers: &csv::StringRecord) -> Result<Self, BankFormatError> {
+        let position = |name: &str| headers.iter().position(|h| h == name);
+        let mut fields = [0; 8];
+        for (slot, field) in fields.iter_mut().zip(Field::ALL) {
+            *slot = position(field.as_str()).ok_or_else(|| {
+                BankFormatError::Parse(format!("missing column: {}", field.as_str()))
+            })?;
+        }
+        Ok(Columns {
+            fields,
+            checksum: position(ROW_CHECKSUM),
+        })
+    }
+}
+
+impl<R: std::io::Read> CsvReader<R> {
+    fn next_record(&mut self) -> Result<Option<Transaction>, BankFormatError> {
+        if let Some(e) = self.invalid_options.take() {
+            return Err(e);
+        }
+        let columns = match self.columns {
+            Some(columns) => columns,
+            None => {
+                let no_header = self
+                    .rdr
+                    .headers()
+                    .map_err(|e| BankFormatError::Parse(e.to_string()))?
+                    .is_empty();
+                // an empty input has no header and no rows
+                if no_header && self.rdr.is_done() {
+                    return Ok(None);
+                }
+                let headers = self
+                    .rdr
+                    .headers()
+                    .map_err(|e| BankFormatError::Parse(e.to_string()))?;
+                *self.columns.insert(Columns::from_headers(headers)?)
+            }
+        };
+
+        if !self.rdr.read_record(&mut self.record).map_err(|e| {
+            // a row of the wrong length or encoding only spoils that row
+            let pos = match e.kind() {
+                csv::ErrorKind::UnequalLengths { pos, .. } => pos.as_ref(),
+                csv::ErrorKind::Utf8 { pos, .. } => pos.as_ref(),
+                _ => None,
+            };
+            match pos {
+                Some(pos) => BankFormatError::ParseAt {
+                    record: pos.record() as usize,
+                    line: Some(pos.line()),
+                    field: String::new(),
+                    detail: e.to_string(),
+                },
+                None => BankFormatError::Parse(e.to_string()),
+            }
+        })? {
+            return Ok(None);
+        }
+        // the header is record 0, so data rows are numbered from 1
+        let position = self.record.position();
+        let record = position.map_or(0, |p| p.record() as usize);
+        let line = position.map(|p| p.line());
+        let fields: Vec<&str> = columns.fields.iter().map(|&i| &self.record[i]).collect();
+        if let Some(at) = columns.checksum {
+            let cells = self
+                .record
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != at)
+                .map(|(_, cell)| cell);
+            let actual = format!("{:08x}", row_checksum(cells));
+            if !self.record[at].eq_ignore_ascii_case(&actual) {
+                return Err(BankFormatError::ParseAt {
+                    record,
+                    line,
+                    field: ROW_CHECKSUM.to_string(),
+                    detail: format!(
+                        "row_checksum mismatch for tx_id {}: expected {}, computed {}",
+                        fields[0], &self.record[at], actual
+                    ),
+                });
+            }
+        }
+        self.opts
+            .parse_transaction(&fields)
+            .map(Some)
+            .map_err(|e| e.at(record, line))
+    }
+}
+
+impl<R: std::io::Read> Iterator for CsvReader<R> {
+    type Item = Result<Transaction, BankFormatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_record().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// A CSV layout that packs several transactions into each row as repeated column
+/// groups: `tx_id_1,tx_type_1,...,description_1,tx_id_2,...`.
+///
+/// Each group holds the eight [`CsvFormat`] columns. A row may end early on a group
+/// boundary, and a group whose cells are all empty is padding and is skipped.
+#[derive(Debug, Clone)]
+pub struct WideCsvFormat {
+    /// Number of column groups per row.
+    pub groups: usize,
+    /// Options applied when parsing each group.
+    pub options: CsvOptions,
+}
+
+impl WideCsvFormat {
+    /// A layout with `groups` transactions per row and default [`CsvOptions`].
+    ///
+    /// # Panics
+    /// Panics if `groups` is zero.
+    pub fn new(groups: usize) -> Self {
+        assert!(groups > 0, "groups must be positive");
+        WideCsvFormat {
+            groups,
+            options: CsvOptions::default(),
+        }
+    }
+
+    /// Read all transactions, unpacking every group of every row in order.
+    pub fn read_all<R: std::io::Read>(
+        &self,
+        r: &mut R,
+    ) -> Result<Vec<Transaction>, BankFormatError> {
+        self.options.check_radix()?;
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(self.options.delimiter)
+            .quote(self.options.quote)
+            .flexible(true)
+            .from_reader(r);
         let mut transactions = Vec::new();
 
-        for result in rdr.records() {
+        for (row, result) in rdr.records().enumerate() {
             let record = result.map_err(|e| BankFormatError::Parse(e.to_string()))?;
-            if record.len() != 8 {
+            let fields: Vec<&str> = record.iter().collect();
+            if fields.len() > self.groups * Field::ALL.len() {
                 return Err(BankFormatError::Parse(format!(
-                    "expected 8 fields, got {}",
-                    record.len()
+                    "row {}: expected at most {} fields, got {}",
+                    row + 1,
+                    self.groups * Field::ALL.len(),
+                    fields.len()
                 )));
             }
-
-            transactions.push(Transaction {
-                tx_id: record[0]
-                    .parse()
-                    .map_err(|_| BankFormatError::Parse("tx_id".into()))?,
-                tx_type: match &record[1] {
-                    "DEPOSIT" => TxType::Deposit,
-                    "TRANSFER" => TxType::Transfer,
-                    "WITHDRAWAL" => TxType::Withdrawal,
-                    other => {
-                        return Err(BankFormatError::Parse(format!("unknown tx_type: {other}")));
-                    }
-                },
-                from_user_id: record[2]
-                    .parse()
-                    .map_err(|_| BankFormatError::Parse("from_user_id".into()))?,
-                to_user_id: record[3]
-                    .parse()
-                    .map_err(|_| BankFormatError::Parse("to_user_id".into()))?,
-                amount: record[4]
-                    .parse()
-                    .map_err(|_| BankFormatError::Parse("amount".into()))?,
-                timestamp: record[5]
-                    .parse()
-                    .map_err(|_| BankFormatError::Parse("timestamp".into()))?,
-                status: match &record[6] {
-                    "SUCCESS" => Status::Success,
-                    "FAILURE" => Status::Failure,
-                    "PENDING" => Status::Pending,
-                    other => {
-                        return Err(BankFormatError::Parse(format!("unknown status: {other}")));
-                    }
-                },
-                description: record[7].to_string(),
-            });
+            for group in fields.chunks(Field::ALL.len()) {
+                if group.len() != Field::ALL.len() {
+                    return Err(BankFormatError::Parse(format!(
+                        "row {}: incomplete trailing group of {} fields",
+                        row + 1,
+                        group.len()
+                    )));
+                }
+                if group.iter().all(|cell| cell.is_empty()) {
+                    continue;
+                }
+                let tx = self
+                    .options
+                    .parse_transaction(group)
+                    .map_err(|e| e.at(row + 1, record.position().map(|p| p.line())))?;
+                transactions.push(tx);
+            }
         }
 
         Ok(transactions)
     }
 
-    fn write_all<W: std::io::Write>(
+    /// Write all transactions, `groups` per row. A short last row is padded with empty groups.
+    pub fn write_all<W: std::io::Write>(
+        &self,
         w: &mut W,
         records: &[Transaction],
     ) -> Result<(), BankFormatError> {
-        let mut wtr = csv::Writer::from_writer(w);
-        wtr.write_record([
-            "tx_id",
-            "tx_type",
-            "from_user_id",
-            "to_user_id",
-            "amount",
-            "timestamp",
-            "status",
-            "description",
-        ])
-        .map_err(|e| BankFormatError::Csv(e))?;
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(self.options.delimiter)
+            .quote(self.options.quote)
+            .from_writer(w);
+        let header = (1..=self.groups)
+            .flat_map(|i| Field::ALL.iter().map(move |field| format!("{field}_{i}")));
+        wtr.write_record(header).map_err(BankFormatError::Csv)?;
 
-        for tx in records {
-            wtr.write_record(&[
-                tx.tx_id.to_string(),
-                tx.tx_type.to_string(),
-                tx.from_user_id.to_string(),
-                tx.to_user_id.to_string(),
-                tx.amount.to_string(),
-                tx.timestamp.to_string(),
-                tx.status.to_string(),
-                tx.description.clone(),
-            ])
-            .map_err(|e| BankFormatError::Csv(e))?;
-        }
-
-        wtr.flush().map_err(|e| BankFormatError::Io(e))?;
+        for chunk in records.chunks(self.groups) {
+            let mut row: Vec<String> = chunk
+                .iter()
+                .flat_map(|tx| Field::ALL.map(|field| tx.field_string(field)))
+                .collect();
+            row.resize(self.groups * Field::ALL.len(), String::new());
+            wtr.write_record(&row).map_err(BankFormatError::Csv)?;
+        }
+
+        wtr.flush().map_err(BankFormatError::Io)?;
         Ok(())
     }
 }
 
+/// Copy a CSV from `r` to `w`, renaming header columns through `mapping`.
+///
+/// Columns not in `mapping` keep their name. Data rows are copied field by field
+/// without being parsed as transactions, so any CSV is accepted.
+pub fn rename_csv_headers<R: std::io::Read, W: std::io::Write>(
+    r: R,
+    w: W,
+    mapping: HashMap<String, String>,
+) -> Result<(), BankFormatError> {
+    let mut rdr = csv::Reader::from_reader(r);
+    let mut wtr = csv::Writer::from_writer(w);
+
+    let headers = rdr.headers().map_err(BankFormatError::Csv)?;
+    wtr.write_record(
+        headers
+            .iter()
+            .map(|name| mapping.get(name).map_or(name, String::as_str)),
+    )
+    .map_err(BankFormatError::Csv)?;
+
+    let mut record = csv::ByteRecord::new();
+    while rdr
+        .read_byte_record(&mut record)
+        .map_err(BankFormatError::Csv)?
+    {
+        wtr.write_byte_record(&record)
+            .map_err(BankFormatError::Csv)?;
+    }
+    wtr.flush().map_err(BankFormatError::Io)?;
+    Ok(())
+}
+
+/// CRC-32 of a row's cells, each followed by a `0x1f` unit separator.
+fn row_checksum<'a>(cells: impl Iterator<Item = &'a str>) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for cell in cells {
+        hasher.update(cell.as_bytes());
+        hasher.update(&[0x1f]);
+    }
+    hasher.finalize()
+}
+
+/// Remove a leading currency symbol or a trailing three-letter currency code.
+fn strip_currency(s: &str) -> &str {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix(['$', '€', '£', '¥']) {
+        return rest.trim_start();
+    }
+    match s.rsplit_once(' ') {
+        Some((value, code)) if code.len() == 3 && code.bytes().all(|b| b.is_ascii_uppercase()) => {
+            value.trim_end()
+        }
+        _ => s,
+    }
+}
+
+/// Parse an unsigned integer in the given base.
+fn parse_u64(s: &str, radix: u32) -> Option<u64> {
+    if radix == 10 {
+        return s.parse().ok();
+    }
+    u64::from_str_radix(strip_radix_prefix(s, radix), radix).ok()
+}
+
+/// Parse a signed integer in the given base.
+fn parse_i64(s: &str, radix: u32) -> Option<i64> {
+    if radix == 10 {
+        return s.parse().ok();
+    }
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let magnitude = u64::from_str_radix(strip_radix_prefix(digits, radix), radix).ok()? as i128;
+    i64::try_from(if negative { -magnitude } else { magnitude }).ok()
+}
+
+fn strip_radix_prefix(s: &str, radix: u32) -> &str {
+    match radix {
+        16 => s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s),
+        _ => s,
+    }
+}
+
+/// Parse a decimal string of major units into minor units with `places` fraction digits.
+///
+/// Returns `None` if the string is malformed, has more fraction digits than `places`,
+/// or overflows `i64`.
+fn parse_decimal(s: &str, places: u32, separator: char, rounding: RoundingMode) -> Option<i64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (int_part, frac_part) = digits.split_once(separator).unwrap_or((digits, ""));
+    if int_part.is_empty()
+        || !int_part.bytes().all(|b| b.is_ascii_digit())
+        || !frac_part.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let (frac_part, excess) = frac_part.split_at(frac_part.len().min(places as usize));
+    let round_up = match rounding {
+        _ if excess.is_empty() => false,
+        RoundingMode::Reject => return None,
+        RoundingMode::HalfUp => excess.as_bytes()[0] >= b'5',
+        RoundingMode::Truncate => false,
+    };
+
+    let scale = 10i64.checked_pow(places)?;
+    let mut value = int_part.parse::<i64>().ok()?.checked_mul(scale)?;
+    if !frac_part.is_empty() {
+        let padding = 10i64.pow(places - frac_part.len() as u32);
+        value = value.checked_add(frac_part.parse::<i64>().ok()? * padding)?;
+    }
+    if round_up {
+        value = value.checked_add(1)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Format minor units as a decimal string of major units with `places` fraction digits.
+///
+/// Works on the digit string, so the result is exact for every `i64`.
+fn format_decimal(amount: i64, places: u32) -> String {
+    let places = places as usize;
+    let digits = format!("{:0>width$}", amount.unsigned_abs(), width = places + 1);
+    let (int_part, frac_part) = digits.split_at(digits.len() - places);
+    let sign = if amount < 0 { "-" } else { "" };
+    if places == 0 {
+        format!("{sign}{int_part}")
+    } else {
+        format!("{sign}{int_part}.{frac_part}")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,6 +721,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_all_empty_input() {
+        assert_eq!(CsvFormat::read_all(&mut "".as_bytes()).unwrap(), vec![]);
+        // a header alone is still checked for the required columns
+        assert!(CsvFormat::read_all(&mut "tx_id\n".as_bytes()).is_err());
+    }
+
     #[test]
     fn test_invalid_parse_cases() {
         let cases: Vec<(&str, &str)> = vec![
@@ -187,11 +760,338 @@ mod tests {
         for (bad_csv, expected_msg) in cases {
             let mut cursor = Cursor::new(bad_csv);
             match CsvFormat::read_all(&mut cursor) {
-                Err(BankFormatError::Parse(msg)) => {
+                Err(BankFormatError::ParseAt { detail: msg, .. }) => {
                     assert!(msg.contains(expected_msg), "got: {}", msg);
                 }
+                other => panic!("expected ParseAt error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_decimal_comma_amount() {
+        let csv = "tx_id;tx_type;from_user_id;to_user_id;amount;timestamp;status;description\n\
+                   1;DEPOSIT;0;42;10,00;1234567890;SUCCESS;test\n\
+                   2;DEPOSIT;0;42;0,5;1234567890;SUCCESS;test\n";
+        let opts = CsvOptions {
+            delimiter: b';',
+            decimal_places: Some(2),
+            decimal_separator: ',',
+            ..CsvOptions::default()
+        };
+
+        let transactions = CsvFormat::read_all_with(&mut Cursor::new(csv), &opts).unwrap();
+        assert_eq!(transactions[0].amount, 1000);
+        assert_eq!(transactions[1].amount, 50);
+
+        // the default separator rejects a comma
+        let opts = CsvOptions {
+            decimal_separator: '.',
+            ..opts
+        };
+        match CsvFormat::read_all_with(&mut Cursor::new(csv), &opts) {
+            Err(BankFormatError::ParseAt { detail: msg, .. }) => {
+                assert!(msg.contains("amount"), "got: {}", msg)
+            }
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_rejects_unsupported_radix() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+        for radix in [0, 37] {
+            let opts = CsvOptions {
+                numeric_radix: radix,
+                ..CsvOptions::default()
+            };
+            match CsvFormat::read_all_with(&mut Cursor::new(csv), &opts) {
+                Err(BankFormatError::Parse(msg)) => {
+                    assert_eq!(msg, format!("unsupported numeric_radix {}", radix))
+                }
                 other => panic!("expected Parse error, got {:?}", other),
             }
         }
     }
+
+    #[test]
+    fn test_read_hex_numeric_fields() {
+        let opts = CsvOptions {
+            numeric_radix: 16,
+            ..CsvOptions::default()
+        };
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   0x1F4,TRANSFER,0xa,2A,0x1F4,1234567890,SUCCESS,test\n";
+
+        let transactions = CsvFormat::read_all_with(&mut Cursor::new(csv), &opts).unwrap();
+        assert_eq!(transactions[0].tx_id, 500);
+        assert_eq!(transactions[0].from_user_id, 10);
+        assert_eq!(transactions[0].to_user_id, 42);
+        assert_eq!(transactions[0].amount, 500);
+
+        let bad = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,0xZZ,1234567890,SUCCESS,test\n";
+        match CsvFormat::read_all_with(&mut Cursor::new(bad), &opts) {
+            Err(BankFormatError::ParseAt { detail: msg, .. }) => {
+                assert!(msg.contains("amount"), "got: {}", msg)
+            }
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lenient_enums_roundtrip_unknown_values() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,REFUND,10,20,1000,1234567890,REVERSED,test\n";
+        let opts = CsvOptions {
+            lenient_enums: true,
+            ..CsvOptions::default()
+        };
+
+        let transactions = CsvFormat::read_all_with(&mut Cursor::new(csv), &opts).unwrap();
+        assert_eq!(transactions[0].tx_type, TxType::Other("REFUND".into()));
+        assert_eq!(transactions[0].status, Status::Other("REVERSED".into()));
+
+        let mut buf = Vec::new();
+        CsvFormat::write_all(&mut buf, &transactions).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), csv);
+
+        assert!(CsvFormat::read_all(&mut Cursor::new(csv)).is_err());
+    }
+
+    #[test]
+    fn test_strip_currency() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,$1000,1234567890,SUCCESS,test\n\
+                   2,DEPOSIT,0,42,1000 USD,1234567890,SUCCESS,test\n";
+        let opts = CsvOptions {
+            strip_currency: true,
+            ..CsvOptions::default()
+        };
+
+        let transactions = CsvFormat::read_all_with(&mut Cursor::new(csv), &opts).unwrap();
+        assert_eq!(transactions[0].amount, 1000);
+        assert_eq!(transactions[1].amount, 1000);
+
+        assert!(CsvFormat::read_all(&mut Cursor::new(csv)).is_err());
+    }
+
+    #[test]
+    fn test_system_id_as_blank_roundtrip() {
+        let withdrawal = Transaction {
+            tx_id: 2,
+            tx_type: TxType::Withdrawal,
+            from_user_id: 42,
+            to_user_id: 0,
+            ..expected_transaction()
+        };
+        let original = vec![expected_transaction(), withdrawal];
+        let mut buf = Vec::new();
+        let write_opts = CsvWriteOptions {
+            system_id_as_blank: true,
+            ..CsvWriteOptions::default()
+        };
+        CsvFormat::write_all_with(&mut buf, &original, &write_opts).unwrap();
+
+        let csv = String::from_utf8(buf).unwrap();
+        assert!(csv.contains("1,DEPOSIT,,42,"));
+        assert!(csv.contains("2,WITHDRAWAL,42,,"));
+
+        let read_opts = CsvOptions {
+            system_id_as_blank: true,
+            ..CsvOptions::default()
+        };
+        let transactions = CsvFormat::read_all_with(&mut Cursor::new(&csv), &read_opts).unwrap();
+        assert_eq!(transactions, original);
+
+        assert!(CsvFormat::read_all(&mut Cursor::new(&csv)).is_err());
+    }
+
+    #[test]
+    fn test_rename_csv_headers() {
+        let csv = make_valid_csv();
+        let mapping = HashMap::from([("tx_id".to_string(), "id".to_string())]);
+        let mut out = Vec::new();
+        rename_csv_headers(csv.as_bytes(), &mut out, mapping).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let (header, rows) = out.split_once('\n').unwrap();
+        assert_eq!(
+            header,
+            "id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description"
+        );
+        assert_eq!(rows, csv.split_once('\n').unwrap().1);
+    }
+
+    #[test]
+    fn test_decimal_rounding_modes() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,10.005,1234567890,SUCCESS,test\n\
+                   2,DEPOSIT,0,42,-10.0049,1234567890,SUCCESS,test\n";
+        let amounts = |rounding| {
+            let opts = CsvOptions {
+                decimal_places: Some(2),
+                rounding,
+                ..CsvOptions::default()
+            };
+            CsvFormat::read_all_with(&mut Cursor::new(csv), &opts)
+                .map(|txs| txs.iter().map(|tx| tx.amount).collect::<Vec<_>>())
+        };
+
+        assert!(amounts(RoundingMode::Reject).is_err());
+        assert_eq!(amounts(RoundingMode::HalfUp).unwrap(), vec![1001, -1000]);
+        assert_eq!(amounts(RoundingMode::Truncate).unwrap(), vec![1000, -1000]);
+    }
+
+    #[test]
+    fn test_wide_csv_two_groups() {
+        let csv = "tx_id_1,tx_type_1,from_user_id_1,to_user_id_1,amount_1,timestamp_1,status_1,description_1,\
+                   tx_id_2,tx_type_2,from_user_id_2,to_user_id_2,amount_2,timestamp_2,status_2,description_2\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test,\
+                   2,TRANSFER,10,20,500,1234567891,PENDING,second\n";
+        let wide = WideCsvFormat::new(2);
+        let transactions = wide.read_all(&mut Cursor::new(csv)).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0], expected_transaction());
+        assert_eq!(transactions[1].tx_id, 2);
+        assert_eq!(transactions[1].status, Status::Pending);
+
+        // an odd record count pads the last row, which reads back without the padding
+        let records = vec![expected_transaction(); 3];
+        let mut buf = Vec::new();
+        wide.write_all(&mut buf, &records).unwrap();
+        assert_eq!(wide.read_all(&mut Cursor::new(buf)).unwrap(), records);
+    }
+
+    #[test]
+    fn test_wide_csv_incomplete_group() {
+        let csv = "tx_id_1,tx_type_1,from_user_id_1,to_user_id_1,amount_1,timestamp_1,status_1,description_1,\
+                   tx_id_2,tx_type_2,from_user_id_2,to_user_id_2,amount_2,timestamp_2,status_2,description_2\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test,2,TRANSFER,10\n";
+        match WideCsvFormat::new(2).read_all(&mut Cursor::new(csv)) {
+            Err(BankFormatError::Parse(msg)) => {
+                assert!(msg.contains("incomplete trailing group"), "got: {}", msg)
+            }
+            other => panic!("expected Parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_row_checksum_roundtrip_and_corruption() {
+        let original = vec![expected_transaction()];
+        let opts = CsvWriteOptions {
+            row_checksum: true,
+            ..CsvWriteOptions::default()
+        };
+        let mut buf = Vec::new();
+        CsvFormat::write_all_with(&mut buf, &original, &opts).unwrap();
+        let csv = String::from_utf8(buf).unwrap();
+        assert!(csv.starts_with("tx_id,"));
+        assert!(csv.lines().next().unwrap().ends_with(",row_checksum"));
+        assert_eq!(
+            CsvFormat::read_all(&mut Cursor::new(&csv)).unwrap(),
+            original
+        );
+
+        let corrupted = csv.replace(",1000,", ",1001,");
+        match CsvFormat::read_all(&mut Cursor::new(corrupted)) {
+            Err(BankFormatError::ParseAt { detail: msg, .. }) => {
+                assert!(msg.contains("row_checksum"), "got: {}", msg)
+            }
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_single_quote_character() {
+        let input = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                     1,DEPOSIT,0,42,1000,1234567890,SUCCESS,'rent, March'\n";
+        let opts = CsvOptions {
+            quote: b'\'',
+            ..CsvOptions::default()
+        };
+        let txs = CsvFormat::read_all_with(&mut input.as_bytes(), &opts).unwrap();
+        assert_eq!(txs[0].description, "rent, March");
+
+        let write_opts = CsvWriteOptions {
+            quote: b'\'',
+            ..CsvWriteOptions::default()
+        };
+        let mut buf = Vec::new();
+        CsvFormat::write_all_with(&mut buf, &txs, &write_opts).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), input);
+    }
+
+    #[test]
+    fn test_amount_major_column() {
+        assert_eq!(format_decimal(1000, 2), "10.00");
+        assert_eq!(format_decimal(-5, 2), "-0.05");
+        assert_eq!(format_decimal(7, 0), "7");
+        assert_eq!(format_decimal(i64::MIN, 2), "-92233720368547758.08");
+
+        let opts = CsvWriteOptions {
+            amount_major: AmountMajor::Alongside(2),
+            ..CsvWriteOptions::default()
+        };
+        let mut buf = Vec::new();
+        CsvFormat::write_all_with(&mut buf, &[expected_transaction()], &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "tx_id,tx_type,from_user_id,to_user_id,amount,amount_major,timestamp,status,description\n\
+             1,DEPOSIT,0,42,1000,10.00,1234567890,SUCCESS,test\n"
+        );
+
+        let opts = CsvWriteOptions {
+            amount_major: AmountMajor::Instead(2),
+            ..CsvWriteOptions::default()
+        };
+        let mut buf = Vec::new();
+        CsvFormat::write_all_with(&mut buf, &[expected_transaction()], &opts).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains(",amount_major,") && out.contains(",42,10.00,"));
+    }
+
+    #[test]
+    fn test_read_iter_yields_error_mid_stream() {
+        let input = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                     1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                     2,DEPOSIT,0,42,oops,1234567890,SUCCESS,test\n\
+                     3,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+        let results: Vec<_> = CsvFormat::read_iter(input.as_bytes()).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), &expected_transaction());
+        match &results[1] {
+            Err(e @ BankFormatError::ParseAt { record, field, .. }) => {
+                assert_eq!((*record, field.as_str()), (2, "amount"));
+                assert_eq!(
+                    e.to_string(),
+                    "Parse error: record 2 (line 3): invalid amount 'oops'"
+                );
+            }
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_columns_mapped_by_header_name() {
+        let input = "status,amount,extra,tx_id,description,timestamp,to_user_id,from_user_id,tx_type\n\
+                     SUCCESS,1000,ignored,1,test,1234567890,42,0,DEPOSIT\n";
+        let txs = CsvFormat::read_all(&mut input.as_bytes()).unwrap();
+        assert_eq!(txs, vec![expected_transaction()]);
+
+        let missing = "tx_id,tx_type,from_user_id,to_user_id,timestamp,status,description\n\
+                       1,DEPOSIT,0,42,1234567890,SUCCESS,test\n";
+        match CsvFormat::read_all(&mut missing.as_bytes()) {
+            Err(BankFormatError::Parse(msg)) => assert_eq!(msg, "missing column: amount"),
+            other => panic!("expected missing column error, got {:?}", other),
+        }
+
+        let short_row = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                         1,DEPOSIT,0,42\n";
+        assert!(matches!(
+            CsvFormat::read_all(&mut short_row.as_bytes()),
+            Err(BankFormatError::ParseAt { record: 1, .. })
+        ));
+    }
 }
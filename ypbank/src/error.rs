@@ -1,4 +1,5 @@
 //! Error types for the ypbank library.
+use crate::Field;
 use std::fmt;
 
 /// All errors that can occur during parsing or serialization of transaction records.
@@ -10,8 +11,25 @@ pub enum BankFormatError {
     Csv(csv::Error),
     /// A general parse error with a description.
     Parse(String),
+    /// A record failed to parse, with its position in the input.
+    ParseAt {
+        /// 1-based number of the record: the data row for CSV, the record ordinal for text
+        /// and JSON.
+        record: usize,
+        /// 1-based line on which the record starts, when known.
+        line: Option<u64>,
+        /// Name of the offending field, as in [`Field::as_str`]. Empty when the record
+        /// as a whole is malformed, e.g. a CSV row with too few cells.
+        field: String,
+        /// What is wrong, e.g. `invalid amount 'notanumber'`.
+        detail: String,
+    },
     /// The binary data is invalid or corrupted.
     InvalidBinary(String),
+    /// The requested format or combination of formats is not available.
+    UnsupportedFormat(String),
+    /// The records parsed but violate a semantic rule.
+    Validation(String),
 }
 
 impl fmt::Display for BankFormatError {
@@ -20,13 +38,67 @@ impl fmt::Display for BankFormatError {
             BankFormatError::Io(e) => write!(f, "IO error: {}", e),
             BankFormatError::Csv(e) => write!(f, "CSV error: {}", e),
             BankFormatError::Parse(msg) => write!(f, "Parse error: {}", msg),
+            BankFormatError::ParseAt {
+                record,
+                line: Some(line),
+                detail,
+                ..
+            } => write!(
+                f,
+                "Parse error: record {} (line {}): {}",
+                record, line, detail
+            ),
+            BankFormatError::ParseAt {
+                record,
+                line: None,
+                detail,
+                ..
+            } => write!(f, "Parse error: record {}: {}", record, detail),
             BankFormatError::InvalidBinary(msg) => write!(f, "Invalid binary format: {}", msg),
+            BankFormatError::UnsupportedFormat(msg) => write!(f, "Unsupported format: {}", msg),
+            BankFormatError::Validation(msg) => write!(f, "Validation error: {}", msg),
         }
     }
 }
 
 impl std::error::Error for BankFormatError {}
 
+/// A field of a record failed to parse; the position is added by the reader.
+#[derive(Debug)]
+pub(crate) struct FieldError {
+    pub(crate) field: Field,
+    pub(crate) detail: String,
+}
+
+impl FieldError {
+    /// `value` is not a valid value for the column called `name`.
+    pub(crate) fn invalid(field: Field, name: &str, value: &str) -> Self {
+        FieldError {
+            field,
+            detail: format!("invalid {name} '{value}'"),
+        }
+    }
+
+    /// Wrap an error returned while parsing `field`, e.g. by `TxType::from_name`.
+    pub(crate) fn from_error(field: Field, e: BankFormatError) -> Self {
+        let detail = match e {
+            BankFormatError::Parse(msg) => msg,
+            other => other.to_string(),
+        };
+        FieldError { field, detail }
+    }
+
+    /// Attach the position of the record in the input.
+    pub(crate) fn at(self, record: usize, line: Option<u64>) -> BankFormatError {
+        BankFormatError::ParseAt {
+            record,
+            line,
+            field: self.field.as_str().to_string(),
+            detail: self.detail,
+        }
+    }
+}
+
 impl From<std::io::Error> for BankFormatError {
     fn from(e: std::io::Error) -> Self {
         BankFormatError::Io(e)
@@ -0,0 +1,782 @@
+//! JSON encodings of transaction records.
+use crate::error::{BankFormatError, FieldError};
+use crate::{BankFormat, Field, Status, Transaction, TxId, TxType};
+use serde::de::{
+    DeserializeSeed, Deserializer, Error as _, IgnoredAny, MapAccess, SeqAccess, Unexpected,
+    Visitor,
+};
+use serde_json::{Value, json};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// A top-level JSON array of transaction objects:
+/// `[{"tx_id": 1, "tx_type": "DEPOSIT", ...}, ...]`.
+///
+/// Each object carries the same fields as the other formats, with `tx_type` and
+/// `status` as their uppercase names. The whole array is parsed before the first
+/// record is yielded. With [`JsonOptions::envelope`] the array is instead read from
+/// inside an enclosing object.
+pub struct JsonFormat;
+
+/// Options controlling how [`JsonFormat`] reads and writes its array.
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    /// Expect the array inside an envelope object, e.g.
+    /// `{"meta": {...}, "transactions": [...]}`, rather than as the whole document.
+    /// Defaults to `false`.
+    pub envelope: bool,
+    /// JSON pointer (RFC 6901) to the array within the envelope. Defaults to
+    /// `/transactions`. Everything else in the envelope is ignored when reading.
+    pub pointer: String,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions {
+            envelope: false,
+            pointer: "/transactions".to_string(),
+        }
+    }
+}
+
+impl BankFormat for JsonFormat {
+    fn read_iter<R: Read>(r: R) -> impl Iterator<Item = Result<Transaction, BankFormatError>> {
+        JsonFormat::read_iter_with(r, &JsonOptions::default())
+    }
+
+    fn write_all<W: Write>(w: &mut W, records: &[Transaction]) -> Result<(), BankFormatError> {
+        JsonFormat::write_all_with(w, records, &JsonOptions::default())
+    }
+}
+
+impl JsonFormat {
+    /// Read all transactions using the given [`JsonOptions`].
+    pub fn read_all_with<R: Read>(
+        r: &mut R,
+        opts: &JsonOptions,
+    ) -> Result<Vec<Transaction>, BankFormatError> {
+        JsonFormat::read_iter_with(r, opts).collect()
+    }
+
+    /// Stream transactions using the given [`JsonOptions`].
+    ///
+    /// With [`JsonOptions::envelope`], a pointer that does not lead to an array yields
+    /// a single [`BankFormatError::Parse`] naming it.
+    pub fn read_iter_with<R: Read>(
+        r: R,
+        opts: &JsonOptions,
+    ) -> impl Iterator<Item = Result<Transaction, BankFormatError>> + use<R> {
+        let values = if opts.envelope {
+            serde_json::from_reader::<_, Value>(r)
+                .map_err(|e| BankFormatError::Parse(e.to_string()))
+                .and_then(
+                    |mut doc| match doc.pointer_mut(&opts.pointer).map(Value::take) {
+                        Some(Value::Array(values)) => Ok(values),
+                        Some(_) => Err(BankFormatError::Parse(format!(
+                            "{} is not an array",
+                            opts.pointer
+                        ))),
+                        None => Err(BankFormatError::Parse(format!(
+                            "no transaction array at {}",
+                            opts.pointer
+                        ))),
+                    },
+                )
+        } else {
+            serde_json::from_reader::<_, Vec<Value>>(r)
+                .map_err(|e| BankFormatError::Parse(e.to_string()))
+        };
+        let items: Vec<_> = match values {
+            Ok(values) => values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| from_value(value, i + 1))
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        items.into_iter()
+    }
+
+    /// Check that `r` holds a well-formed array of transactions without building them.
+    ///
+    /// Structure and field types are checked as by [`BankFormat::read_all`], but no
+    /// [`Transaction`] or description `String` is allocated, so this is cheaper for a
+    /// pass whose records are discarded. Returns the number of records.
+    pub fn validate_only<R: Read>(r: R) -> Result<usize, BankFormatError> {
+        let tracker = Tracker::default();
+        let mut de = serde_json::Deserializer::from_reader(r);
+        de.deserialize_seq(ArrayShape(&tracker))
+            .and_then(|count| de.end().map(|()| count))
+            .map_err(|e| tracker.error(e))
+    }
+
+    /// Write all transactions using the given [`JsonOptions`].
+    ///
+    /// With [`JsonOptions::envelope`] the array is nested at the pointer inside an
+    /// object that also holds the number of records as a top-level `count`, e.g.
+    /// `{"count": 2, "transactions": [...]}`.
+    pub fn write_all_with<W: Write>(
+        w: &mut W,
+        records: &[Transaction],
+        opts: &JsonOptions,
+    ) -> Result<(), BankFormatError> {
+        if !opts.envelope {
+            w.write_all(b"[")?;
+            for (i, tx) in records.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut *w, &to_value(tx))
+                    .map_err(|e| BankFormatError::Parse(e.to_string()))?;
+            }
+            w.write_all(b"]")?;
+            w.flush()?;
+            return Ok(());
+        }
+
+        let mut doc = json!({ "count": records.len() });
+        let mut slot = &mut doc;
+        for token in opts.pointer.split('/').skip(1) {
+            let key = token.replace("~1", "/").replace("~0", "~");
+            if !slot.is_object() {
+                *slot = json!({});
+            }
+            slot = slot
+                .as_object_mut()
+                .expect("slot was just made an object")
+                .entry(key)
+                .or_insert(Value::Null);
+        }
+        *slot = Value::Array(records.iter().map(to_value).collect());
+        serde_json::to_writer(&mut *w, &doc).map_err(|e| BankFormatError::Parse(e.to_string()))?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// A single JSON object mapping each `tx_id` to its transaction:
+/// `{"1": {"tx_id": 1, ...}, "2": {...}}`.
+///
+/// Keys are decimal ids and must be unique; each value carries the same fields as
+/// the other formats, with `tx_type` and `status` as their uppercase names.
+pub struct JsonMapFormat;
+
+impl BankFormat for JsonMapFormat {
+    /// The whole object is parsed before the first record is yielded, as duplicate
+    /// keys can only be detected once all of them are known.
+    fn read_iter<R: Read>(mut r: R) -> impl Iterator<Item = Result<Transaction, BankFormatError>> {
+        let items: Vec<_> = match JsonMapFormat::read_all(&mut r) {
+            Ok(transactions) => transactions.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        items.into_iter()
+    }
+
+    fn read_all<R: Read>(r: &mut R) -> Result<Vec<Transaction>, BankFormatError> {
+        let mut de = serde_json::Deserializer::from_reader(r);
+        let entries = de
+            .deserialize_map(TxMapVisitor)
+            .map_err(|e| BankFormatError::Parse(e.to_string()))?;
+        de.end()
+            .map_err(|e| BankFormatError::Parse(e.to_string()))?;
+        entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, (key, value))| {
+                let tx = from_value(value, i + 1)?;
+                if tx.tx_id != key {
+                    return Err(FieldError {
+                        field: Field::TxId,
+                        detail: format!("tx_id {} does not match its key {key}", tx.tx_id),
+                    }
+                    .at(i + 1, None));
+                }
+                Ok(tx)
+            })
+            .collect()
+    }
+
+    fn write_all<W: Write>(w: &mut W, records: &[Transaction]) -> Result<(), BankFormatError> {
+        let mut seen: HashSet<TxId> = HashSet::new();
+        w.write_all(b"{")?;
+        for (i, tx) in records.iter().enumerate() {
+            if !seen.insert(tx.tx_id) {
+                return Err(BankFormatError::Parse(format!(
+                    "duplicate tx_id {} can not be written as a map key",
+                    tx.tx_id
+                )));
+            }
+            if i > 0 {
+                w.write_all(b",")?;
+            }
+            write!(w, "\"{}\":", tx.tx_id)?;
+            serde_json::to_writer(&mut *w, &to_value(tx))
+                .map_err(|e| BankFormatError::Parse(e.to_string()))?;
+        }
+        w.write_all(b"}")?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// Collects the entries of a tx_id map in document order, rejecting repeated ids.
+struct TxMapVisitor;
+
+impl<'de> Visitor<'de> for TxMapVisitor {
+    type Value = Vec<(TxId, Value)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON object mapping tx_id to transaction")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut seen: HashSet<TxId> = HashSet::new();
+        let mut entries = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let tx_id: TxId = key
+                .parse()
+                .map_err(|_| A::Error::custom(format!("invalid tx_id key: {key:?}")))?;
+            if !seen.insert(tx_id) {
+                return Err(A::Error::custom(format!("duplicate tx_id key: {tx_id}")));
+            }
+            entries.push((tx_id, map.next_value()?));
+        }
+        Ok(entries)
+    }
+}
+
+impl JsonMapFormat {
+    /// Check that `r` holds a well-formed tx_id map without building the transactions.
+    ///
+    /// Like [`JsonFormat::validate_only`], this applies the checks of
+    /// [`BankFormat::read_all`], including unique keys that match each `tx_id`, without
+    /// allocating records or descriptions. Returns the number of records.
+    pub fn validate_only<R: Read>(r: R) -> Result<usize, BankFormatError> {
+        let tracker = Tracker::default();
+        let mut de = serde_json::Deserializer::from_reader(r);
+        de.deserialize_map(MapShape(&tracker))
+            .and_then(|count| de.end().map(|()| count))
+            .map_err(|e| tracker.error(e))
+    }
+}
+
+/// Where a `validate_only` pass is, so an error can name the record it occurred in.
+#[derive(Default)]
+struct Tracker {
+    /// 1-based index of the record being checked, `0` outside of any record.
+    record: Cell<usize>,
+    /// Field of the current record being checked, if any.
+    field: Cell<Option<Field>>,
+}
+
+impl Tracker {
+    /// Move on to the record with the given 1-based index.
+    fn start(&self, record: usize) {
+        self.record.set(record);
+        self.field.set(None);
+    }
+
+    fn error(&self, e: serde_json::Error) -> BankFormatError {
+        match self.record.get() {
+            0 => BankFormatError::Parse(e.to_string()),
+            record => BankFormatError::ParseAt {
+                record,
+                line: None,
+                field: self.field.get().map_or("", |f| f.as_str()).to_string(),
+                detail: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Checks a top-level array of transaction objects, counting them.
+struct ArrayShape<'a>(&'a Tracker);
+
+impl<'de> Visitor<'de> for ArrayShape<'_> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON array of transactions")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<usize, A::Error> {
+        let mut count = 0;
+        loop {
+            self.0.start(count + 1);
+            if seq.next_element_seed(RecordShape(self.0))?.is_none() {
+                break;
+            }
+            count += 1;
+        }
+        self.0.start(0);
+        Ok(count)
+    }
+}
+
+/// Checks a tx_id map like [`TxMapVisitor`], counting its entries.
+struct MapShape<'a>(&'a Tracker);
+
+impl<'de> Visitor<'de> for MapShape<'_> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON object mapping tx_id to transaction")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<usize, A::Error> {
+        let mut seen: HashSet<TxId> = HashSet::new();
+        let mut count = 0;
+        loop {
+            self.0.start(count + 1);
+            let Some(key) = map.next_key_seed(KeyShape)? else {
+                break;
+            };
+            if !seen.insert(key) {
+                return Err(A::Error::custom(format!("duplicate tx_id key: {key}")));
+            }
+            let tx_id = map.next_value_seed(RecordShape(self.0))?;
+            if tx_id != key {
+                self.0.field.set(Some(Field::TxId));
+                return Err(A::Error::custom(format!(
+                    "tx_id {tx_id} does not match its key {key}"
+                )));
+            }
+            count += 1;
+        }
+        self.0.start(0);
+        Ok(count)
+    }
+}
+
+/// Checks one transaction object, yielding its `tx_id`.
+struct RecordShape<'a>(&'a Tracker);
+
+impl<'de> DeserializeSeed<'de> for RecordShape<'_> {
+    type Value = TxId;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<TxId, D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RecordShape<'_> {
+    type Value = TxId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a transaction object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<TxId, A::Error> {
+        let mut seen = [false; 8];
+        let mut tx_id = 0;
+        while let Some(key) = map.next_key_seed(FieldName)? {
+            let Some(i) = key else {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            };
+            seen[i] = true;
+            self.0.field.set(Some(Field::ALL[i]));
+            let value = map.next_value_seed(ValueShape(Field::ALL[i]))?;
+            if Field::ALL[i] == Field::TxId {
+                tx_id = value;
+            }
+        }
+        self.0.field.set(None);
+        match seen.iter().position(|present| !present) {
+            Some(i) => {
+                self.0.field.set(Some(Field::ALL[i]));
+                Err(A::Error::custom(format!(
+                    "missing field {}",
+                    Field::ALL[i].as_str()
+                )))
+            }
+            None => Ok(tx_id),
+        }
+    }
+}
+
+/// A key of a transaction object, as its index in [`Field::ALL`], or `None` for an
+/// unknown key.
+struct FieldName;
+
+impl<'de> DeserializeSeed<'de> for FieldName {
+    type Value = Option<usize>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldName {
+    type Value = Option<usize>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a field name")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Field::ALL.iter().position(|field| field.as_str() == v))
+    }
+}
+
+/// Checks the value of one field without keeping it. Yields the value of `tx_id`,
+/// and `0` for every other field.
+struct ValueShape(Field);
+
+impl<'de> DeserializeSeed<'de> for ValueShape {
+    type Value = TxId;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<TxId, D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ValueShape {
+    type Value = TxId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Field::TxId => f.write_str("an unsigned integer tx_id"),
+            Field::TxType | Field::Status => write!(f, "a {} name", self.0.as_str()),
+            Field::Description => f.write_str("a string description"),
+            field => write!(f, "an integer {}", field.as_str()),
+        }
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<TxId, E> {
+        match self.0 {
+            Field::TxId => Ok(v),
+            Field::TxType | Field::Status | Field::Description => {
+                Err(E::invalid_type(Unexpected::Unsigned(v), &self))
+            }
+            _ if i64::try_from(v).is_err() => Err(E::invalid_value(Unexpected::Unsigned(v), &self)),
+            _ => Ok(0),
+        }
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<TxId, E> {
+        match self.0 {
+            Field::TxId => {
+                TxId::try_from(v).map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+            }
+            Field::TxType | Field::Status | Field::Description => {
+                Err(E::invalid_type(Unexpected::Signed(v), &self))
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<TxId, E> {
+        match self.0 {
+            Field::TxType => match TxType::from_name(v, false) {
+                Ok(_) => Ok(0),
+                Err(_) => Err(E::custom(format!("unknown tx_type: {v}"))),
+            },
+            Field::Status => match Status::from_name(v, false) {
+                Ok(_) => Ok(0),
+                Err(_) => Err(E::custom(format!("unknown status: {v}"))),
+            },
+            Field::Description => Ok(0),
+            _ => Err(E::invalid_type(Unexpected::Str(v), &self)),
+        }
+    }
+}
+
+/// A decimal tx_id map key.
+struct KeyShape;
+
+impl<'de> DeserializeSeed<'de> for KeyShape {
+    type Value = TxId;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<TxId, D::Error> {
+        d.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyShape {
+    type Value = TxId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a tx_id key")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<TxId, E> {
+        v.parse()
+            .map_err(|_| E::custom(format!("invalid tx_id key: {v:?}")))
+    }
+}
+
+pub(crate) fn to_value(tx: &Transaction) -> Value {
+    json!({
+        "tx_id": tx.tx_id,
+        "tx_type": tx.tx_type.to_string(),
+        "from_user_id": tx.from_user_id,
+        "to_user_id": tx.to_user_id,
+        "amount": tx.amount,
+        "timestamp": tx.timestamp,
+        "status": tx.status.to_string(),
+        "description": tx.description,
+    })
+}
+
+/// Build a transaction from its JSON object, the `record`-th (1-based) of the input.
+fn from_value(value: Value, record: usize) -> Result<Transaction, BankFormatError> {
+    let Value::Object(obj) = value else {
+        return Err(BankFormatError::ParseAt {
+            record,
+            line: None,
+            field: String::new(),
+            detail: "expected an object".to_string(),
+        });
+    };
+    let fields = || -> Result<Transaction, FieldError> {
+        let get = |field: Field| {
+            obj.get(field.as_str()).ok_or_else(|| FieldError {
+                field,
+                detail: format!("missing field {}", field.as_str()),
+            })
+        };
+        let mismatch = |field: Field, expected: &str| FieldError {
+            field,
+            detail: format!("{} is not {expected}", field.as_str()),
+        };
+        let int = |field: Field| {
+            get(field)?
+                .as_i64()
+                .ok_or_else(|| mismatch(field, "an integer"))
+        };
+        let string = |field: Field| {
+            get(field)?
+                .as_str()
+                .ok_or_else(|| mismatch(field, "a string"))
+        };
+
+        Ok(Transaction {
+            tx_id: get(Field::TxId)?
+                .as_u64()
+                .ok_or_else(|| mismatch(Field::TxId, "an unsigned integer"))?,
+            tx_type: TxType::from_name(string(Field::TxType)?, false)
+                .map_err(|e| FieldError::from_error(Field::TxType, e))?,
+            from_user_id: int(Field::FromUserId)?,
+            to_user_id: int(Field::ToUserId)?,
+            amount: int(Field::Amount)?,
+            timestamp: int(Field::Timestamp)?,
+            status: Status::from_name(string(Field::Status)?, false)
+                .map_err(|e| FieldError::from_error(Field::Status, e))?,
+            description: string(Field::Description)?.to_string(),
+        })
+    };
+    fields().map_err(|e| e.at(record, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Transaction> {
+        vec![
+            Transaction {
+                tx_id: 2,
+                tx_type: TxType::Transfer,
+                from_user_id: 10,
+                to_user_id: 20,
+                amount: 500,
+                timestamp: 1234567891,
+                status: Status::Pending,
+                description: "second \"quoted\"".to_string(),
+            },
+            Transaction {
+                tx_id: 10,
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                to_user_id: 42,
+                amount: 1000,
+                timestamp: 1234567890,
+                status: Status::Success,
+                description: "test".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_json_map_roundtrip() {
+        let mut buf = Vec::new();
+        JsonMapFormat::write_all(&mut buf, &sample()).unwrap();
+
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(json["2"]["tx_type"], "TRANSFER");
+        assert_eq!(json["10"]["status"], "SUCCESS");
+        assert_eq!(
+            JsonMapFormat::read_all(&mut buf.as_slice()).unwrap(),
+            sample()
+        );
+    }
+
+    #[test]
+    fn test_json_map_duplicate_key() {
+        let entry = r#"{"tx_id":1,"tx_type":"DEPOSIT","from_user_id":0,"to_user_id":42,"amount":1000,"timestamp":1234567890,"status":"SUCCESS","description":"test"}"#;
+        let input = format!(r#"{{"1":{entry},"1":{entry}}}"#);
+        match JsonMapFormat::read_all(&mut input.as_bytes()) {
+            Err(BankFormatError::Parse(msg)) => assert!(msg.contains("duplicate tx_id key: 1")),
+            other => panic!("expected duplicate key error, got {:?}", other),
+        }
+
+        let mut duplicates = sample();
+        duplicates[1].tx_id = 2;
+        assert!(JsonMapFormat::write_all(&mut Vec::new(), &duplicates).is_err());
+    }
+
+    #[test]
+    fn test_json_envelope_nested_path() {
+        let input = r#"{"meta":{"page":1},"data":{"items":[{"tx_id":1,"tx_type":"DEPOSIT","from_user_id":0,"to_user_id":42,"amount":1000,"timestamp":1234567890,"status":"SUCCESS","description":"test"}]}}"#;
+        let opts = JsonOptions {
+            envelope: true,
+            pointer: "/data/items".to_string(),
+        };
+        let transactions = JsonFormat::read_all_with(&mut input.as_bytes(), &opts).unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].to_user_id, 42);
+
+        let mut buf = Vec::new();
+        JsonFormat::write_all_with(&mut buf, &sample(), &opts).unwrap();
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(json["count"], 2);
+        assert_eq!(json["data"]["items"][1]["tx_id"], 10);
+        assert_eq!(
+            JsonFormat::read_all_with(&mut buf.as_slice(), &opts).unwrap(),
+            sample()
+        );
+
+        let opts = JsonOptions {
+            envelope: true,
+            ..JsonOptions::default()
+        };
+        let mut buf = Vec::new();
+        JsonFormat::write_all_with(&mut buf, &sample(), &opts).unwrap();
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 2);
+        assert_eq!(json["transactions"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_json_envelope_missing_path() {
+        let opts = JsonOptions {
+            envelope: true,
+            ..JsonOptions::default()
+        };
+        let input = r#"{"meta":{"page":1},"items":[]}"#;
+        match JsonFormat::read_all_with(&mut input.as_bytes(), &opts) {
+            Err(BankFormatError::Parse(msg)) => {
+                assert_eq!(msg, "no transaction array at /transactions")
+            }
+            other => panic!("expected Parse error, got {:?}", other),
+        }
+
+        let input = r#"{"transactions":{"tx_id":1}}"#;
+        assert!(matches!(
+            JsonFormat::read_all_with(&mut input.as_bytes(), &opts),
+            Err(BankFormatError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_only() {
+        let mut array = Vec::new();
+        JsonFormat::write_all(&mut array, &sample()).unwrap();
+        assert_eq!(JsonFormat::validate_only(array.as_slice()).unwrap(), 2);
+        let mut map = Vec::new();
+        JsonMapFormat::write_all(&mut map, &sample()).unwrap();
+        assert_eq!(JsonMapFormat::validate_only(map.as_slice()).unwrap(), 2);
+
+        let entry = r#"{"tx_id":1,"tx_type":"DEPOSIT","from_user_id":0,"to_user_id":42,"amount":"1000","timestamp":1234567890,"status":"SUCCESS","description":"test"}"#;
+        let wrong_amount = format!("[{entry}]");
+        assert!(JsonFormat::read_all(&mut wrong_amount.as_bytes()).is_err());
+        match JsonFormat::validate_only(wrong_amount.as_bytes()) {
+            Err(BankFormatError::ParseAt {
+                record: 1,
+                field,
+                detail,
+                ..
+            }) => {
+                assert_eq!(field, "amount");
+                assert!(
+                    detail.starts_with("invalid type: string \"1000\", expected an integer amount"),
+                    "got: {}",
+                    detail
+                )
+            }
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+        let wrong_amount = format!(r#"{{"1":{entry}}}"#);
+        assert!(JsonMapFormat::validate_only(wrong_amount.as_bytes()).is_err());
+
+        let missing = r#"[{"tx_id":1,"tx_type":"DEPOSIT"}]"#;
+        match JsonFormat::validate_only(missing.as_bytes()) {
+            Err(BankFormatError::ParseAt {
+                record: 1,
+                field,
+                detail,
+                ..
+            }) => {
+                assert_eq!(field, "from_user_id");
+                assert!(
+                    detail.starts_with("missing field from_user_id"),
+                    "got: {}",
+                    detail
+                )
+            }
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+        let mismatched = String::from_utf8(map)
+            .unwrap()
+            .replacen(r#""2":"#, r#""3":"#, 1);
+        assert!(matches!(
+            JsonMapFormat::validate_only(mismatched.as_bytes()),
+            Err(BankFormatError::ParseAt { record: 1, .. })
+        ));
+        assert!(matches!(
+            JsonMapFormat::read_all(&mut mismatched.as_bytes()),
+            Err(BankFormatError::ParseAt { record: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_json_array_roundtrip_and_errors() {
+        let mut buf = Vec::new();
+        JsonFormat::write_all(&mut buf, &sample()).unwrap();
+
+        let json: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(json[0]["tx_type"], "TRANSFER");
+        assert_eq!(json[1]["status"], "SUCCESS");
+        assert_eq!(JsonFormat::read_all(&mut buf.as_slice()).unwrap(), sample());
+
+        let unknown = r#"[{"tx_id":1,"tx_type":"REFUND","from_user_id":0,"to_user_id":42,"amount":1000,"timestamp":1234567890,"status":"SUCCESS","description":"test"}]"#;
+        match JsonFormat::read_all(&mut unknown.as_bytes()) {
+            Err(BankFormatError::ParseAt {
+                record: 1, field, ..
+            }) => assert_eq!(field, "tx_type"),
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+        let written = String::from_utf8(buf).unwrap();
+        let missing = format!(
+            r#"{},{{"tx_id":1,"tx_type":"DEPOSIT"}}]"#,
+            &written[..written.len() - 1]
+        );
+        let err = JsonFormat::read_all(&mut missing.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse error: record 3: missing field from_user_id"
+        );
+        match err {
+            BankFormatError::ParseAt {
+                record: 3,
+                line: None,
+                field,
+                ..
+            } => assert_eq!(field, "from_user_id"),
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+    }
+}
@@ -1,13 +1,28 @@
 //! # ypbank
 //!
 //! A library for parsing serializing and comparing bank transaction records
-//! in multiple formats: CSV, binary, and plain text.
+//! in multiple formats: CSV, binary, plain text, and JSON.
+pub mod analysis;
 pub mod bin_format;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod csv_format;
 pub mod error;
+#[cfg(feature = "json")]
+pub mod json_format;
+pub mod reconcile;
+pub mod store;
+pub mod stream;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transform;
 pub mod txt_format;
-use std::collections::HashMap;
+pub mod validate;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::convert::Infallible;
 use std::fmt;
+use std::str::FromStr;
 
 pub use csv_format::CsvFormat;
 use error::BankFormatError;
@@ -17,6 +32,7 @@ pub type TxId = u64;
 
 /// Represents a single bank transaction.
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transaction {
     /// Unique transaction identifier.
     pub tx_id: TxId,
@@ -42,6 +58,7 @@ impl fmt::Display for TxType {
             TxType::Deposit => write!(f, "DEPOSIT"),
             TxType::Transfer => write!(f, "TRANSFER"),
             TxType::Withdrawal => write!(f, "WITHDRAWAL"),
+            TxType::Other(name) => write!(f, "{}", name),
         }
     }
 }
@@ -52,12 +69,62 @@ impl fmt::Display for Status {
             Status::Success => write!(f, "SUCCESS"),
             Status::Failure => write!(f, "FAILURE"),
             Status::Pending => write!(f, "PENDING"),
+            Status::Other(name) => write!(f, "{}", name),
         }
     }
 }
 
+impl TxType {
+    /// Parse a tx_type name. With `lenient`, unknown names become [`TxType::Other`].
+    pub(crate) fn from_name(s: &str, lenient: bool) -> Result<Self, BankFormatError> {
+        match s {
+            "DEPOSIT" => Ok(TxType::Deposit),
+            "TRANSFER" => Ok(TxType::Transfer),
+            "WITHDRAWAL" => Ok(TxType::Withdrawal),
+            other if lenient => Ok(TxType::Other(other.to_string())),
+            other => Err(BankFormatError::Parse(format!("unknown tx_type: {other}"))),
+        }
+    }
+}
+
+impl FromStr for TxType {
+    type Err = BankFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TxType::from_name(s, false)
+    }
+}
+
+impl Status {
+    /// Parse a status name. With `lenient`, unknown names become [`Status::Other`].
+    pub(crate) fn from_name(s: &str, lenient: bool) -> Result<Self, BankFormatError> {
+        match s {
+            "SUCCESS" => Ok(Status::Success),
+            "FAILURE" => Ok(Status::Failure),
+            "PENDING" => Ok(Status::Pending),
+            other if lenient => Ok(Status::Other(other.to_string())),
+            other => Err(BankFormatError::Parse(format!("unknown status: {other}"))),
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = BankFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Status::from_name(s, false)
+    }
+}
+
 /// The type of a bank transaction.
+///
+/// With the `serde` feature, variants use their [`fmt::Display`] spelling (`"DEPOSIT"`).
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "UPPERCASE")
+)]
 pub enum TxType {
     /// Funds deposited into the system.
     Deposit,
@@ -65,10 +132,20 @@ pub enum TxType {
     Transfer,
     /// Funds withdrawn from the system.
     Withdrawal,
+    /// A type not known to this version, preserved verbatim when reading with lenient enums.
+    #[cfg_attr(feature = "serde", serde(untagged))]
+    Other(String),
 }
 
 /// The status of a bank transaction.
+///
+/// With the `serde` feature, variants use their [`fmt::Display`] spelling (`"SUCCESS"`).
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "UPPERCASE")
+)]
 pub enum Status {
     /// Transaction completed successfully.
     Success,
@@ -76,6 +153,159 @@ pub enum Status {
     Failure,
     /// Transaction is pending processing.
     Pending,
+    /// A status not known to this version, preserved verbatim when reading with lenient enums.
+    #[cfg_attr(feature = "serde", serde(untagged))]
+    Other(String),
+}
+
+/// A named field of a [`Transaction`].
+///
+/// The string form matches the CSV column names (`tx_id`, `amount`, ...).
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Field {
+    /// [`Transaction::tx_id`].
+    TxId,
+    /// [`Transaction::tx_type`].
+    TxType,
+    /// [`Transaction::from_user_id`].
+    FromUserId,
+    /// [`Transaction::to_user_id`].
+    ToUserId,
+    /// [`Transaction::amount`].
+    Amount,
+    /// [`Transaction::timestamp`].
+    Timestamp,
+    /// [`Transaction::status`].
+    Status,
+    /// [`Transaction::description`].
+    Description,
+}
+
+impl Field {
+    /// All fields in canonical column order.
+    pub const ALL: [Field; 8] = [
+        Field::TxId,
+        Field::TxType,
+        Field::FromUserId,
+        Field::ToUserId,
+        Field::Amount,
+        Field::Timestamp,
+        Field::Status,
+        Field::Description,
+    ];
+
+    /// The field name as used in CSV headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Field::TxId => "tx_id",
+            Field::TxType => "tx_type",
+            Field::FromUserId => "from_user_id",
+            Field::ToUserId => "to_user_id",
+            Field::Amount => "amount",
+            Field::Timestamp => "timestamp",
+            Field::Status => "status",
+            Field::Description => "description",
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Field {
+    type Err = BankFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.as_str() == s)
+            .ok_or_else(|| BankFormatError::Parse(format!("unknown field: {s}")))
+    }
+}
+
+impl Transaction {
+    /// Return the fields whose values differ between `self` and `other`, in column order.
+    pub fn differing_fields(&self, other: &Transaction) -> Vec<Field> {
+        Field::ALL
+            .into_iter()
+            .filter(|field| match field {
+                Field::TxId => self.tx_id != other.tx_id,
+                Field::TxType => self.tx_type != other.tx_type,
+                Field::FromUserId => self.from_user_id != other.from_user_id,
+                Field::ToUserId => self.to_user_id != other.to_user_id,
+                Field::Amount => self.amount != other.amount,
+                Field::Timestamp => self.timestamp != other.timestamp,
+                Field::Status => self.status != other.status,
+                Field::Description => self.description != other.description,
+            })
+            .collect()
+    }
+
+    /// Render the value of `field` as it appears in text formats.
+    pub fn field_string(&self, field: Field) -> String {
+        match field {
+            Field::TxId => self.tx_id.to_string(),
+            Field::TxType => self.tx_type.to_string(),
+            Field::FromUserId => self.from_user_id.to_string(),
+            Field::ToUserId => self.to_user_id.to_string(),
+            Field::Amount => self.amount.to_string(),
+            Field::Timestamp => self.timestamp.to_string(),
+            Field::Status => self.status.to_string(),
+            Field::Description => self.description.clone(),
+        }
+    }
+
+    /// A stable 64-bit hash of every field not listed in `ignore`.
+    ///
+    /// Uses FNV-1a over each compared field's name and [`Transaction::field_string`]
+    /// value, so the result is the same across runs, platforms and crate builds and
+    /// changes whenever a compared field changes.
+    pub fn content_hash(&self, ignore: &[Field]) -> u64 {
+        let mut hash = FNV_OFFSET;
+        for field in Field::ALL.into_iter().filter(|f| !ignore.contains(f)) {
+            let value = self.field_string(field);
+            hash = fnv1a(hash, field.as_str().as_bytes());
+            hash = fnv1a(hash, &(value.len() as u64).to_le_bytes());
+            hash = fnv1a(hash, value.as_bytes());
+        }
+        hash
+    }
+
+    /// Check the invariants every record should satisfy on its own.
+    ///
+    /// A `DEPOSIT` must have `from_user_id == 0`, a `WITHDRAWAL` must have
+    /// `to_user_id == 0`, and `amount` must not be negative. See
+    /// [`validate::validate_all`] to also reject duplicate ids.
+    pub fn validate(&self) -> Result<(), validate::ValidationError> {
+        let rule = match self.tx_type {
+            TxType::Deposit if self.from_user_id != 0 => Some(validate::DEPOSIT_FROM_SYSTEM),
+            TxType::Withdrawal if self.to_user_id != 0 => Some(validate::WITHDRAWAL_TO_SYSTEM),
+            _ if self.amount < 0 => Some("amount must be >= 0"),
+            _ => None,
+        };
+        match rule {
+            Some(rule) => Err(validate::ValidationError {
+                tx_id: self.tx_id,
+                rule: rule.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continue a 64-bit FNV-1a hash over `bytes`. Start from [`FNV_OFFSET`].
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
 }
 
 /// A trait for reading and writing transaction records in a specific format.
@@ -84,8 +314,45 @@ pub enum Status {
 /// Uses [`std::io::Read`] and [`std::io::Write`]
 /// works with files, stdin, in-memory buffers, or any other IO source.
 pub trait BankFormat: Sized {
+    /// Read transactions one at a time from the given reader.
+    ///
+    /// Records are parsed as the iterator is advanced, so memory use does not grow
+    /// with the input. A malformed record is yielded as an error in its place and
+    /// ends the iteration; EOF simply ends it.
+    fn read_iter<R: std::io::Read>(
+        r: R,
+    ) -> impl Iterator<Item = Result<Transaction, BankFormatError>>;
+
     /// Read all transactions from the given reader.
-    fn read_all<R: std::io::Read>(r: &mut R) -> Result<Vec<Transaction>, BankFormatError>;
+    ///
+    /// Collects [`BankFormat::read_iter`], stopping at the first error.
+    fn read_all<R: std::io::Read>(r: &mut R) -> Result<Vec<Transaction>, BankFormatError> {
+        Self::read_iter(r).collect()
+    }
+
+    /// Read all transactions, skipping records that fail to parse.
+    ///
+    /// Returns the parsed transactions and, for each skipped record, its 0-based
+    /// index in the input together with the error. Formats that can resume after a
+    /// bad record (CSV, text, JSON) continue with the next one. Binary input can not
+    /// be resynchronized once a record's framing is broken, so reading stops at the
+    /// first such error, which is reported last; everything before it is returned.
+    /// Errors that affect the whole input, such as I/O errors or a missing CSV
+    /// column, also stop reading.
+    fn read_all_lenient<R: std::io::Read>(
+        r: &mut R,
+    ) -> (Vec<Transaction>, Vec<(usize, BankFormatError)>) {
+        let mut transactions = Vec::new();
+        let mut skipped = Vec::new();
+        for (index, tx) in Self::read_iter(r).enumerate() {
+            match tx {
+                Ok(tx) => transactions.push(tx),
+                Err(e) => skipped.push((index, e)),
+            }
+        }
+        (transactions, skipped)
+    }
+
     /// Write all transactions to the given writer.
     fn write_all<W: std::io::Write>(
         w: &mut W,
@@ -93,6 +360,29 @@ pub trait BankFormat: Sized {
     ) -> Result<(), BankFormatError>;
 }
 
+/// Drive `next_record` to EOF for [`BankFormat::read_all_lenient`].
+///
+/// A [`BankFormatError::ParseAt`] only concerns its own record, so reading goes on
+/// after it; any other error ends reading.
+pub(crate) fn collect_lenient(
+    mut next_record: impl FnMut() -> Result<Option<Transaction>, BankFormatError>,
+) -> (Vec<Transaction>, Vec<(usize, BankFormatError)>) {
+    let mut transactions = Vec::new();
+    let mut skipped = Vec::new();
+    for index in 0.. {
+        match next_record() {
+            Ok(Some(tx)) => transactions.push(tx),
+            Ok(None) => break,
+            Err(e @ BankFormatError::ParseAt { .. }) => skipped.push((index, e)),
+            Err(e) => {
+                skipped.push((index, e));
+                break;
+            }
+        }
+    }
+    (transactions, skipped)
+}
+
 /// Convert transaction records from one format to another.
 ///
 /// Reads from `r` using format `From` and writes to `w` using format `To`.
@@ -109,10 +399,486 @@ where
     From: BankFormat,
     To: BankFormat,
 {
-    let transactions = From::read_all(r)?;
+    convert_with::<From, To>(r, w, Some)
+}
+
+/// Convert transaction records, passing each one through `f` on the way.
+///
+/// `f` returns `None` to drop a record or `Some` to keep it, possibly modified.
+/// The survivors are written with `To` in input order. If every record is dropped,
+/// `w` still receives a well-formed empty output, e.g. a CSV header only.
+///
+/// # Example
+/// ```no_run
+///  // convert_with::<CsvFormat, BinFormat>(&mut input, &mut output, |tx| {
+///  //     (tx.tx_type == TxType::Transfer).then_some(tx)
+///  // })?;
+/// ```
+pub fn convert_with<From, To>(
+    r: &mut impl std::io::Read,
+    w: &mut impl std::io::Write,
+    mut f: impl FnMut(Transaction) -> Option<Transaction>,
+) -> Result<(), BankFormatError>
+where
+    From: BankFormat,
+    To: BankFormat,
+{
+    let mut transactions = Vec::new();
+    for tx in From::read_iter(r) {
+        transactions.extend(f(tx?));
+    }
+    To::write_all(w, &transactions)
+}
+
+/// Options for [`convert_for_sharing`].
+#[derive(Debug, Clone)]
+pub struct ShareOptions {
+    /// Salt for [`transform::anonymize_users`].
+    pub salt: String,
+    /// How descriptions are redacted, see [`transform::redact_descriptions`].
+    pub redact_mode: transform::RedactMode,
+}
+
+/// Convert records like [`convert`], pseudonymizing user ids and redacting
+/// descriptions on the way, to produce a dataset that can be shared.
+///
+/// All other fields, including amounts, are written unchanged.
+pub fn convert_for_sharing<From, To>(
+    r: &mut impl std::io::Read,
+    w: &mut impl std::io::Write,
+    opts: &ShareOptions,
+) -> Result<(), BankFormatError>
+where
+    From: BankFormat,
+    To: BankFormat,
+{
+    let mut transactions = From::read_all(r)?;
+    transform::anonymize_users(&mut transactions, &opts.salt);
+    transform::redact_descriptions(&mut transactions, opts.redact_mode);
     To::write_all(w, &transactions)
 }
 
+/// Write only the `FAILURE` records of `r` to `w`, ordered by timestamp.
+///
+/// Records with equal timestamps keep their input order.
+pub fn export_failures<From, To>(
+    r: &mut impl std::io::Read,
+    w: &mut impl std::io::Write,
+) -> Result<(), BankFormatError>
+where
+    From: BankFormat,
+    To: BankFormat,
+{
+    let mut failures: Vec<Transaction> = From::read_all(r)?
+        .into_iter()
+        .filter(|tx| tx.status == Status::Failure)
+        .collect();
+    failures.sort_by(|a, b| transform::SortKey::Timestamp.compare(a, b));
+    To::write_all(w, &failures)
+}
+
+/// A transaction format selected at runtime.
+///
+/// Dispatches to the corresponding [`BankFormat`] implementation for callers that
+/// only know the format after parsing arguments or configuration.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum FormatKind {
+    /// [`CsvFormat`].
+    Csv,
+    /// [`txt_format::TxtFormat`].
+    Txt,
+    /// [`bin_format::BinFormat`].
+    Bin,
+    /// [`json_format::JsonFormat`]. Available with the `json` feature.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl FormatKind {
+    /// Stream transactions from the given reader in this format, see
+    /// [`BankFormat::read_iter`].
+    pub fn read_iter<'a, R: std::io::Read + 'a>(
+        self,
+        r: R,
+    ) -> Box<dyn Iterator<Item = Result<Transaction, BankFormatError>> + 'a> {
+        match self {
+            FormatKind::Csv => Box::new(CsvFormat::read_iter(r)),
+            FormatKind::Txt => Box::new(txt_format::TxtFormat::read_iter(r)),
+            FormatKind::Bin => Box::new(bin_format::BinFormat::read_iter(r)),
+            #[cfg(feature = "json")]
+            FormatKind::Json => Box::new(json_format::JsonFormat::read_iter(r)),
+        }
+    }
+
+    /// Read all transactions from the given reader in this format.
+    pub fn read_all<R: std::io::Read>(
+        self,
+        r: &mut R,
+    ) -> Result<Vec<Transaction>, BankFormatError> {
+        match self {
+            FormatKind::Csv => CsvFormat::read_all(r),
+            FormatKind::Txt => txt_format::TxtFormat::read_all(r),
+            FormatKind::Bin => bin_format::BinFormat::read_all(r),
+            #[cfg(feature = "json")]
+            FormatKind::Json => json_format::JsonFormat::read_all(r),
+        }
+    }
+
+    /// Write all transactions to the given writer in this format.
+    pub fn write_all<W: std::io::Write>(
+        self,
+        w: &mut W,
+        records: &[Transaction],
+    ) -> Result<(), BankFormatError> {
+        match self {
+            FormatKind::Csv => CsvFormat::write_all(w, records),
+            FormatKind::Txt => txt_format::TxtFormat::write_all(w, records),
+            FormatKind::Bin => bin_format::BinFormat::write_all(w, records),
+            #[cfg(feature = "json")]
+            FormatKind::Json => json_format::JsonFormat::write_all(w, records),
+        }
+    }
+
+    /// Start writing transactions one at a time to `w` in this format.
+    ///
+    /// Any header is written right away; the output is complete once
+    /// [`RecordWriter::finish`] returns and matches [`FormatKind::write_all`] over the
+    /// same records.
+    pub fn writer<W: std::io::Write>(self, mut w: W) -> Result<RecordWriter<W>, BankFormatError> {
+        let sink = match self {
+            FormatKind::Csv => {
+                Sink::Csv(Box::new(csv_format::CsvWriteOptions::default().writer(w)?))
+            }
+            FormatKind::Txt => Sink::Txt(w),
+            FormatKind::Bin => {
+                let opts = bin_format::BinOptions::default();
+                bin_format::write_file_header(&mut w, opts.endian)?;
+                Sink::Bin {
+                    w,
+                    opts,
+                    buf: Vec::new(),
+                    prev_id: 0,
+                }
+            }
+            #[cfg(feature = "json")]
+            FormatKind::Json => {
+                w.write_all(b"[")?;
+                Sink::Json(w)
+            }
+        };
+        Ok(RecordWriter { sink, count: 0 })
+    }
+}
+
+/// Writes transactions one at a time, created by [`FormatKind::writer`].
+pub struct RecordWriter<W: std::io::Write> {
+    sink: Sink<W>,
+    /// Number of records written so far.
+    count: usize,
+}
+
+enum Sink<W: std::io::Write> {
+    Csv(Box<csv::Writer<W>>),
+    Txt(W),
+    Bin {
+        w: W,
+        opts: bin_format::BinOptions,
+        buf: Vec<u8>,
+        prev_id: TxId,
+    },
+    #[cfg(feature = "json")]
+    Json(W),
+}
+
+impl<W: std::io::Write> RecordWriter<W> {
+    /// Write one transaction.
+    pub fn write(&mut self, tx: &Transaction) -> Result<(), BankFormatError> {
+        match &mut self.sink {
+            Sink::Csv(wtr) => csv_format::CsvWriteOptions::default().write_row(wtr, tx)?,
+            Sink::Txt(w) => txt_format::write_record(w, self.count + 1, tx)?,
+            Sink::Bin {
+                w,
+                opts,
+                buf,
+                prev_id,
+            } => {
+                bin_format::encode_record(buf, tx, opts, prev_id)?;
+                w.write_all(buf)?;
+            }
+            #[cfg(feature = "json")]
+            Sink::Json(w) => {
+                if self.count > 0 {
+                    w.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut *w, &json_format::to_value(tx))
+                    .map_err(|e| BankFormatError::Parse(e.to_string()))?;
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Write any trailer, flush, and return the inner writer.
+    pub fn finish(self) -> Result<W, BankFormatError> {
+        let mut w = match self.sink {
+            Sink::Csv(wtr) => wtr
+                .into_inner()
+                .map_err(|e| BankFormatError::Io(e.into_error()))?,
+            Sink::Txt(w) | Sink::Bin { w, .. } => w,
+            #[cfg(feature = "json")]
+            Sink::Json(mut w) => {
+                w.write_all(b"]")?;
+                w
+            }
+        };
+        w.flush()?;
+        Ok(w)
+    }
+}
+
+impl FromStr for FormatKind {
+    type Err = BankFormatError;
+
+    /// Parse a format name: `csv`, `txt`, `json`, or `bin` (also accepted as `binary`).
+    /// `json` is only known with the `json` feature.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(FormatKind::Csv),
+            "txt" => Ok(FormatKind::Txt),
+            "bin" | "binary" => Ok(FormatKind::Bin),
+            #[cfg(feature = "json")]
+            "json" => Ok(FormatKind::Json),
+            other => Err(BankFormatError::UnsupportedFormat(other.to_string())),
+        }
+    }
+}
+
+/// Read a stream made of consecutive segments in different formats.
+///
+/// Each entry of `segments` gives the format and byte length of the next segment;
+/// its records are appended to the result in stream order. Returns
+/// [`BankFormatError::Parse`] if the stream ends before a segment is complete.
+/// Bytes after the last declared segment are left unread.
+pub fn read_segments<R: std::io::Read>(
+    segments: Vec<(FormatKind, usize)>,
+    r: &mut R,
+) -> Result<Vec<Transaction>, BankFormatError> {
+    let mut transactions = Vec::new();
+    for (i, (kind, len)) in segments.into_iter().enumerate() {
+        let mut segment = std::io::Read::take(&mut *r, len as u64);
+        transactions.extend(kind.read_all(&mut segment)?);
+        std::io::copy(&mut segment, &mut std::io::sink())?;
+        if segment.limit() > 0 {
+            return Err(BankFormatError::Parse(format!(
+                "segment {} is truncated: expected {} bytes, got {}",
+                i,
+                len,
+                len as u64 - segment.limit()
+            )));
+        }
+    }
+    Ok(transactions)
+}
+
+/// Split a source into one output per UTC calendar day.
+///
+/// The day of a record is its `timestamp` floored to midnight UTC, given to `open` as
+/// epoch milliseconds. `open` is called once per day, in ascending order, and that
+/// day's records are written to the returned writer in format `to`, keeping their
+/// input order.
+pub fn partition_by_day<From, R, W>(
+    r: &mut R,
+    mut open: impl FnMut(i64) -> W,
+    to: FormatKind,
+) -> Result<(), BankFormatError>
+where
+    From: BankFormat,
+    R: std::io::Read,
+    W: std::io::Write,
+{
+    let transactions = From::read_all(r)?;
+    for (day, indices) in analysis::bucket_by_time(&transactions, analysis::MS_PER_DAY) {
+        let records: Vec<Transaction> = indices
+            .into_iter()
+            .map(|i| transactions[i].clone())
+            .collect();
+        to.write_all(&mut open(day), &records)?;
+    }
+    Ok(())
+}
+
+/// Merge several sources, each sorted by [`TxId`], into one sorted output.
+///
+/// Performs a k-way merge with a min-heap and writes the result to `w` in format `to`.
+/// Records with equal ids keep the order of the sources they came from. Returns
+/// [`BankFormatError::Parse`] if any source is not sorted by `tx_id`.
+///
+/// Sources are streamed with [`BankFormat::read_iter`] and each record is written as
+/// soon as it is taken from the heap, so only one pending record per source is held
+/// in memory.
+pub fn merge_sorted<F: BankFormat, R: std::io::Read>(
+    readers: Vec<R>,
+    w: &mut impl std::io::Write,
+    to: FormatKind,
+) -> Result<(), BankFormatError> {
+    let mut shards: Vec<_> = readers.into_iter().map(F::read_iter).collect();
+
+    let mut heap = BinaryHeap::new();
+    for (source, shard) in shards.iter_mut().enumerate() {
+        if let Some(tx) = shard.next().transpose()? {
+            heap.push(Reverse(MergeHead { tx, source }));
+        }
+    }
+
+    let mut out = to.writer(w)?;
+    while let Some(Reverse(MergeHead { tx, source })) = heap.pop() {
+        if let Some(next) = shards[source].next().transpose()? {
+            if next.tx_id < tx.tx_id {
+                return Err(BankFormatError::Parse(format!(
+                    "source {} is not sorted by tx_id: {} follows {}",
+                    source, next.tx_id, tx.tx_id
+                )));
+            }
+            heap.push(Reverse(MergeHead { tx: next, source }));
+        }
+        out.write(&tx)?;
+    }
+    out.finish()?;
+    Ok(())
+}
+
+/// The next record of one [`merge_sorted`] source, ordered by `tx_id` and then by
+/// source index.
+struct MergeHead {
+    tx: Transaction,
+    source: usize,
+}
+
+impl MergeHead {
+    fn key(&self) -> (TxId, usize) {
+        (self.tx.tx_id, self.source)
+    }
+}
+
+impl PartialEq for MergeHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for MergeHead {}
+
+impl PartialOrd for MergeHead {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MergeHead {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Read all transactions from a base64-encoded string using format `F`.
+///
+/// Useful when small transaction sets are injected through environment variables.
+/// Surrounding whitespace is ignored; invalid base64 yields [`BankFormatError::Parse`].
+pub fn read_all_base64<F: BankFormat>(s: &str) -> Result<Vec<Transaction>, BankFormatError> {
+    use base64::Engine;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(s.trim())
+        .map_err(|e| BankFormatError::Parse(format!("invalid base64: {e}")))?;
+    F::read_all(&mut bytes.as_slice())
+}
+
+/// Read all transactions using format `F`, failing once they exceed a memory budget.
+///
+/// The budget is measured approximately as the sum of `size_of::<Transaction>()` and the
+/// description length of every accumulated record. Exceeding `max_bytes` yields
+/// [`BankFormatError::Parse`] naming the budget and the number of records read, without
+/// reading the rest of the input.
+pub fn read_all_bounded<F: BankFormat>(
+    r: &mut impl std::io::Read,
+    max_bytes: usize,
+) -> Result<Vec<Transaction>, BankFormatError> {
+    let mut used = 0usize;
+    let mut transactions = Vec::new();
+    for tx in F::read_iter(r) {
+        let tx = tx?;
+        used += std::mem::size_of::<Transaction>() + tx.description.len();
+        if used > max_bytes {
+            return Err(BankFormatError::Parse(format!(
+                "memory budget of {} bytes exceeded after {} records",
+                max_bytes,
+                transactions.len()
+            )));
+        }
+        transactions.push(tx);
+    }
+    Ok(transactions)
+}
+
+/// Read all transactions using format `F`, reporting unexpectedly large inputs.
+///
+/// Unlike [`read_all_bounded`] this never fails on size: `on_warn` is called at most
+/// once, with the record count at the moment it first exceeds `warn_threshold`
+/// (i.e. `warn_threshold + 1`), as soon as that record is read, and reading continues.
+pub fn read_all_warn<F: BankFormat>(
+    r: &mut impl std::io::Read,
+    warn_threshold: usize,
+    on_warn: impl FnOnce(usize),
+) -> Result<Vec<Transaction>, BankFormatError> {
+    let mut on_warn = Some(on_warn);
+    let mut transactions = Vec::new();
+    for tx in F::read_iter(r) {
+        transactions.push(tx?);
+        if transactions.len() > warn_threshold
+            && let Some(warn) = on_warn.take()
+        {
+            warn(transactions.len());
+        }
+    }
+    Ok(transactions)
+}
+
+/// Read all transactions using format `F`, rejecting repeated ids.
+///
+/// [`BankFormat::read_all`] keeps every record, and a later map keyed by [`TxId`] (as in
+/// [`compare`]) silently keeps only one of them. This fails instead with
+/// [`BankFormatError::Parse`] naming the first repeated id, as soon as it is read.
+pub fn read_all_strict<F: BankFormat>(
+    r: &mut impl std::io::Read,
+) -> Result<Vec<Transaction>, BankFormatError> {
+    let mut seen: HashSet<TxId> = HashSet::new();
+    let mut transactions = Vec::new();
+    for tx in F::read_iter(r) {
+        let tx = tx?;
+        if !seen.insert(tx.tx_id) {
+            return Err(BankFormatError::Parse(format!(
+                "duplicate tx_id: {}",
+                tx.tx_id
+            )));
+        }
+        transactions.push(tx);
+    }
+    Ok(transactions)
+}
+
+/// Read all transactions using format `F` and check them with
+/// [`validate::validate_all`].
+///
+/// A violation yields [`BankFormatError::Validation`] naming the offending `tx_id` and
+/// rule, e.g. `tx 7: DEPOSIT must have from_user_id == 0`.
+pub fn read_all_validated<F: BankFormat>(
+    r: &mut impl std::io::Read,
+) -> Result<Vec<Transaction>, BankFormatError> {
+    let transactions = F::read_all(r)?;
+    validate::validate_all(&transactions)?;
+    Ok(transactions)
+}
+
 /// Compare transaction records from two readers, potentially in different formats.
 ///
 /// Returns [`CompareResult::Identical`] if both sources contain the same transactions
@@ -121,34 +887,184 @@ pub fn compare<F1, F2>(
     r1: &mut impl std::io::Read,
     r2: &mut impl std::io::Read,
 ) -> Result<CompareResult, BankFormatError>
+where
+    F1: BankFormat,
+    F2: BankFormat,
+{
+    compare_with::<F1, F2>(r1, r2, &CompareOptions::default())
+}
+
+/// Options for [`compare_with`].
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// Treat `PENDING` as matching any status on the other side, so a record that
+    /// settled between two snapshots is not reported as differing. All other fields
+    /// must still match.
+    pub pending_wildcard: bool,
+}
+
+impl CompareOptions {
+    /// Whether `a` and `b` are considered the same record under these options.
+    fn matches(&self, a: &Transaction, b: &Transaction) -> bool {
+        if !self.pending_wildcard || (a.status != Status::Pending && b.status != Status::Pending) {
+            return a == b;
+        }
+        a.differing_fields(b)
+            .iter()
+            .all(|field| *field == Field::Status)
+    }
+}
+
+/// Compare like [`compare`], using the given [`CompareOptions`].
+pub fn compare_with<F1, F2>(
+    r1: &mut impl std::io::Read,
+    r2: &mut impl std::io::Read,
+    opts: &CompareOptions,
+) -> Result<CompareResult, BankFormatError>
 where
     F1: BankFormat,
     F2: BankFormat,
 {
     let transactions_one = F1::read_all(r1)?;
     let transactions_two = F2::read_all(r2)?;
+    Ok(compare_records(&transactions_one, &transactions_two, opts))
+}
+
+/// Counts reported to the hook of [`compare_with_progress`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CompareProgress {
+    /// Records read so far, over both sources.
+    pub read: usize,
+    /// Records checked so far, over both sources.
+    pub compared: usize,
+    /// Records to check in total: the distinct ids of source 1 plus those of source 2.
+    /// `0` while the sources are being read.
+    pub total: usize,
+}
+
+/// The result of [`compare_with_progress`].
+#[derive(Debug)]
+pub enum ProgressCompareResult {
+    /// The comparison ran to the end.
+    Completed(CompareResult),
+    /// The hook stopped the comparison after the given progress.
+    Cancelled(CompareProgress),
+}
 
-    let map1: HashMap<TxId, Transaction> =
-        transactions_one.into_iter().map(|t| (t.tx_id, t)).collect();
-    let map2: HashMap<TxId, Transaction> =
-        transactions_two.into_iter().map(|t| (t.tx_id, t)).collect();
+/// Compare like [`compare_with`], reporting progress and allowing cancellation.
+///
+/// `on_progress` is called after every record read from either source, streamed with
+/// [`BankFormat::read_iter`], and then after every record checked. Returning `true`
+/// from it stops the comparison, and the result is
+/// [`ProgressCompareResult::Cancelled`] with the counts at that point.
+pub fn compare_with_progress<F1, F2>(
+    r1: &mut impl std::io::Read,
+    r2: &mut impl std::io::Read,
+    opts: &CompareOptions,
+    mut on_progress: impl FnMut(CompareProgress) -> bool,
+) -> Result<ProgressCompareResult, BankFormatError>
+where
+    F1: BankFormat,
+    F2: BankFormat,
+{
+    let mut progress = CompareProgress::default();
+    let Some(transactions_one) = read_with_progress::<F1>(r1, &mut progress, &mut on_progress)?
+    else {
+        return Ok(ProgressCompareResult::Cancelled(progress));
+    };
+    let Some(transactions_two) = read_with_progress::<F2>(r2, &mut progress, &mut on_progress)?
+    else {
+        return Ok(ProgressCompareResult::Cancelled(progress));
+    };
+
+    let result = compare_records_with(
+        &transactions_one,
+        &transactions_two,
+        opts,
+        &mut |compared, total| {
+            progress.compared = compared;
+            progress.total = total;
+            if on_progress(progress) {
+                Err(progress)
+            } else {
+                Ok(())
+            }
+        },
+    );
+    Ok(match result {
+        Ok(result) => ProgressCompareResult::Completed(result),
+        Err(progress) => ProgressCompareResult::Cancelled(progress),
+    })
+}
+
+/// Read every record of `r`, counting them in `progress.read` and calling `on_progress`
+/// after each one. Returns `Ok(None)` as soon as the hook asks to stop.
+fn read_with_progress<F: BankFormat>(
+    r: impl std::io::Read,
+    progress: &mut CompareProgress,
+    on_progress: &mut impl FnMut(CompareProgress) -> bool,
+) -> Result<Option<Vec<Transaction>>, BankFormatError> {
+    let mut transactions = Vec::new();
+    for tx in F::read_iter(r) {
+        transactions.push(tx?);
+        progress.read += 1;
+        if on_progress(*progress) {
+            return Ok(None);
+        }
+    }
+    Ok(Some(transactions))
+}
+
+/// Compare two already parsed sets of records by `tx_id`.
+fn compare_records(
+    transactions_one: &[Transaction],
+    transactions_two: &[Transaction],
+    opts: &CompareOptions,
+) -> CompareResult {
+    let Ok(result) = compare_records_with::<Infallible>(
+        transactions_one,
+        transactions_two,
+        opts,
+        &mut |_, _| Ok(()),
+    );
+    result
+}
+
+/// Compare two sets of records, calling `on_checked` with the number of records
+/// checked and the total after each one. An error from it stops the comparison.
+fn compare_records_with<E>(
+    transactions_one: &[Transaction],
+    transactions_two: &[Transaction],
+    opts: &CompareOptions,
+    on_checked: &mut dyn FnMut(usize, usize) -> Result<(), E>,
+) -> Result<CompareResult, E> {
+    let map1: HashMap<TxId, &Transaction> = transactions_one.iter().map(|t| (t.tx_id, t)).collect();
+    let map2: HashMap<TxId, &Transaction> = transactions_two.iter().map(|t| (t.tx_id, t)).collect();
 
     let mut missing_in_2 = vec![];
     let mut missing_in_1 = vec![];
     let mut differing = vec![];
+    let total = map1.len() + map2.len();
+    let mut compared = 0;
 
     for (id, tx1) in &map1 {
         match map2.get(id) {
             None => missing_in_2.push(*id),
-            Some(tx2) if tx1 != tx2 => differing.push((*id, tx1.clone(), tx2.clone())),
+            Some(tx2) if !opts.matches(tx1, tx2) => {
+                differing.push((*id, (*tx1).clone(), (*tx2).clone()))
+            }
             _ => {}
         }
+        compared += 1;
+        on_checked(compared, total)?;
     }
 
     for id in map2.keys() {
         if !map1.contains_key(id) {
             missing_in_1.push(*id);
         }
+        compared += 1;
+        on_checked(compared, total)?;
     }
 
     if missing_in_1.is_empty() && missing_in_2.is_empty() && differing.is_empty() {
@@ -162,6 +1078,46 @@ where
     }
 }
 
+/// Write the records that changed between two sources to `w` in format `To`.
+///
+/// Records are compared as in [`compare`]. The output holds the source 2 version of
+/// every record that was added (missing in source 1) or differs, in source 2 order.
+/// Records only present in source 1 are not represented.
+pub fn extract_changes<F1, F2, To>(
+    r1: &mut impl std::io::Read,
+    r2: &mut impl std::io::Read,
+    w: &mut impl std::io::Write,
+) -> Result<(), BankFormatError>
+where
+    F1: BankFormat,
+    F2: BankFormat,
+    To: BankFormat,
+{
+    let transactions_one = F1::read_all(r1)?;
+    let transactions_two = F2::read_all(r2)?;
+
+    let changed: HashSet<TxId> = match compare_records(
+        &transactions_one,
+        &transactions_two,
+        &CompareOptions::default(),
+    ) {
+        CompareResult::Identical => HashSet::new(),
+        CompareResult::Mismatch {
+            missing_in_1,
+            differing,
+            ..
+        } => missing_in_1
+            .into_iter()
+            .chain(differing.into_iter().map(|(id, _, _)| id))
+            .collect(),
+    };
+    let changes: Vec<Transaction> = transactions_two
+        .into_iter()
+        .filter(|tx| changed.contains(&tx.tx_id))
+        .collect();
+    To::write_all(w, &changes)
+}
+
 /// The result of comparing two sets of transaction records.
 #[derive(Debug)]
 pub enum CompareResult {
@@ -173,18 +1129,111 @@ pub enum CompareResult {
         missing_in_1: Vec<TxId>,
         /// Transaction IDs present in source 1 but missing in source 2.
         missing_in_2: Vec<TxId>,
-        //// Transactions present in both sources but with differing fields.
+        /// Transactions present in both sources but with differing fields, as
+        /// `(id, source 1 record, source 2 record)`.
         differing: Vec<(TxId, Transaction, Transaction)>,
     },
 }
 
+/// Compare any number of labeled, already parsed sets of records by `tx_id`.
+///
+/// Reading and decoding stay with the caller, so the sources can come in any mix of
+/// formats. If an id repeats within one source, the last record wins, as in
+/// [`compare`].
+pub fn compare_many(sources: Vec<(&str, Vec<Transaction>)>) -> MultiCompareResult {
+    let labels: Vec<String> = sources.iter().map(|(label, _)| label.to_string()).collect();
+    let mut by_id: BTreeMap<TxId, Vec<Option<Transaction>>> = BTreeMap::new();
+    for (i, (_, transactions)) in sources.into_iter().enumerate() {
+        for tx in transactions {
+            let slots = by_id
+                .entry(tx.tx_id)
+                .or_insert_with(|| vec![None; labels.len()]);
+            slots[i] = Some(tx);
+        }
+    }
+
+    let entries = by_id
+        .into_iter()
+        .map(|(tx_id, slots)| {
+            let mut entry = MultiCompareEntry {
+                tx_id,
+                records: Vec::new(),
+                missing_in: Vec::new(),
+                differing: Vec::new(),
+            };
+            for (label, slot) in labels.iter().zip(slots) {
+                match slot {
+                    Some(tx) => entry.records.push((label.clone(), tx)),
+                    None => entry.missing_in.push(label.clone()),
+                }
+            }
+            if let Some(((_, first), rest)) = entry.records.split_first() {
+                entry.differing = Field::ALL
+                    .into_iter()
+                    .filter(|field| {
+                        rest.iter()
+                            .any(|(_, tx)| first.differing_fields(tx).contains(field))
+                    })
+                    .collect();
+            }
+            entry
+        })
+        .collect();
+    MultiCompareResult { entries }
+}
+
+/// How one `tx_id` appears across the sources of [`compare_many`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MultiCompareEntry {
+    /// The transaction id.
+    pub tx_id: TxId,
+    /// The record of every source that has this id, tagged by source label, in
+    /// source order.
+    pub records: Vec<(String, Transaction)>,
+    /// Labels of the sources without this id, in source order.
+    pub missing_in: Vec<String>,
+    /// Fields whose values are not the same in all of [`MultiCompareEntry::records`].
+    pub differing: Vec<Field>,
+}
+
+/// The result of [`compare_many`]: one entry per `tx_id` seen in any source.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct MultiCompareResult {
+    /// All entries, in ascending `tx_id` order.
+    pub entries: Vec<MultiCompareEntry>,
+}
+
+impl MultiCompareResult {
+    /// Entries whose id is missing from at least one source.
+    pub fn incomplete(&self) -> impl Iterator<Item = &MultiCompareEntry> {
+        self.entries.iter().filter(|e| !e.missing_in.is_empty())
+    }
+
+    /// Entries present in every source but with differing fields.
+    pub fn conflicting(&self) -> impl Iterator<Item = &MultiCompareEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.missing_in.is_empty() && !e.differing.is_empty())
+    }
+
+    /// Whether every source holds the same records.
+    pub fn is_identical(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| e.missing_in.is_empty() && e.differing.is_empty())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::bin_format::BinFormat;
     use crate::csv_format::CsvFormat;
     use crate::txt_format::TxtFormat;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
     use std::io::Cursor;
+    use std::rc::Rc;
 
     fn expected_transaction() -> Transaction {
         Transaction {
@@ -248,6 +1297,175 @@ mod tests {
         assert_eq!(transactions[0], expected_transaction());
     }
 
+    // --- field tests ---
+
+    #[test]
+    fn test_field_string_roundtrip() {
+        for field in Field::ALL {
+            assert_eq!(field.as_str().parse::<Field>().unwrap(), field);
+            assert_eq!(field.to_string(), field.as_str());
+        }
+        assert!(matches!(
+            "amt".parse::<Field>(),
+            Err(BankFormatError::Parse(msg)) if msg.contains("unknown field: amt")
+        ));
+    }
+
+    #[test]
+    fn test_differing_fields() {
+        let tx1 = expected_transaction();
+        let mut tx2 = expected_transaction();
+        assert!(tx1.differing_fields(&tx2).is_empty());
+
+        tx2.amount = 9999;
+        tx2.status = Status::Failure;
+        assert_eq!(
+            tx1.differing_fields(&tx2),
+            vec![Field::Amount, Field::Status]
+        );
+    }
+
+    // --- format kind tests ---
+
+    #[test]
+    fn test_format_kind_from_str() {
+        assert_eq!("csv".parse::<FormatKind>().unwrap(), FormatKind::Csv);
+        assert_eq!("binary".parse::<FormatKind>().unwrap(), FormatKind::Bin);
+        #[cfg(feature = "json")]
+        assert_eq!("json".parse::<FormatKind>().unwrap(), FormatKind::Json);
+
+        let err = "xml".parse::<FormatKind>().unwrap_err();
+        assert!(matches!(&err, BankFormatError::UnsupportedFormat(name) if name == "xml"));
+        assert_eq!(err.to_string(), "Unsupported format: xml");
+    }
+
+    // --- base64 tests ---
+
+    #[test]
+    fn test_read_all_base64_binary() {
+        use base64::Engine;
+
+        let original = vec![expected_transaction()];
+        let mut bin_buf = Vec::new();
+        BinFormat::write_all(&mut bin_buf, &original).unwrap();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&bin_buf);
+
+        let transactions = read_all_base64::<BinFormat>(&format!("{encoded}\n")).unwrap();
+        assert_eq!(transactions, original);
+    }
+
+    #[test]
+    fn test_read_all_base64_invalid() {
+        match read_all_base64::<BinFormat>("not base64!") {
+            Err(BankFormatError::Parse(msg)) => assert!(msg.contains("invalid base64")),
+            other => panic!("expected Parse error, got {:?}", other),
+        }
+    }
+
+    // --- bounded read tests ---
+
+    #[test]
+    fn test_read_all_bounded() {
+        let records = vec![expected_transaction(); 3];
+        let mut buf = Vec::new();
+        BinFormat::write_all(&mut buf, &records).unwrap();
+        let per_record = std::mem::size_of::<Transaction>() + "test".len();
+
+        let parsed = read_all_bounded::<BinFormat>(&mut Cursor::new(&buf), per_record * 3).unwrap();
+        assert_eq!(parsed, records);
+
+        match read_all_bounded::<BinFormat>(&mut Cursor::new(&buf), per_record * 2) {
+            Err(BankFormatError::Parse(msg)) => {
+                assert!(msg.contains("memory budget"), "got: {}", msg);
+                assert!(msg.contains("after 2 records"), "got: {}", msg);
+            }
+            other => panic!("expected Parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_all_warn_fires_once() {
+        let records = vec![expected_transaction(); 5];
+        let mut buf = Vec::new();
+        BinFormat::write_all(&mut buf, &records).unwrap();
+
+        let mut warnings = Vec::new();
+        let parsed =
+            read_all_warn::<BinFormat>(&mut Cursor::new(&buf), 2, |n| warnings.push(n)).unwrap();
+        assert_eq!(parsed, records);
+        assert_eq!(warnings, vec![3]);
+
+        let mut warned = false;
+        read_all_warn::<BinFormat>(&mut Cursor::new(&buf), 5, |_| warned = true).unwrap();
+        assert!(!warned);
+    }
+
+    // --- merge tests ---
+
+    fn shard(ids: &[TxId]) -> Vec<u8> {
+        let records: Vec<Transaction> = ids
+            .iter()
+            .map(|&tx_id| Transaction {
+                tx_id,
+                ..expected_transaction()
+            })
+            .collect();
+        let mut buf = Vec::new();
+        BinFormat::write_all(&mut buf, &records).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_merge_sorted_binary_shards_to_csv() {
+        let shards = vec![
+            Cursor::new(shard(&[1, 4, 7])),
+            Cursor::new(shard(&[2, 5])),
+            Cursor::new(shard(&[3, 6, 8, 9])),
+        ];
+        let mut output = Vec::new();
+        merge_sorted::<BinFormat, _>(shards, &mut output, FormatKind::Csv).unwrap();
+
+        let merged = CsvFormat::read_all(&mut Cursor::new(output)).unwrap();
+        let ids: Vec<TxId> = merged.iter().map(|tx| tx.tx_id).collect();
+        assert_eq!(ids, (1..=9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_record_writer_matches_write_all() {
+        let records: Vec<Transaction> = (1..=3)
+            .map(|tx_id| Transaction {
+                tx_id,
+                ..expected_transaction()
+            })
+            .collect();
+        for kind in [
+            FormatKind::Csv,
+            FormatKind::Txt,
+            FormatKind::Bin,
+            #[cfg(feature = "json")]
+            FormatKind::Json,
+        ] {
+            for records in [&records[..], &[]] {
+                let mut expected = Vec::new();
+                kind.write_all(&mut expected, records).unwrap();
+                let mut out = kind.writer(Vec::new()).unwrap();
+                for tx in records {
+                    out.write(tx).unwrap();
+                }
+                assert_eq!(out.finish().unwrap(), expected, "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_sorted_rejects_unsorted_shard() {
+        let shards = vec![Cursor::new(shard(&[1, 2])), Cursor::new(shard(&[5, 3]))];
+        match merge_sorted::<BinFormat, _>(shards, &mut Vec::new(), FormatKind::Csv) {
+            Err(BankFormatError::Parse(msg)) => assert!(msg.contains("not sorted"), "got: {}", msg),
+            other => panic!("expected Parse error, got {:?}", other),
+        }
+    }
+
     // --- compare tests ---
 
     #[test]
@@ -345,9 +1563,437 @@ mod tests {
                 assert!(missing_in_1.is_empty());
                 assert!(missing_in_2.is_empty());
                 assert_eq!(differing.len(), 1);
-                assert_eq!(differing[0].0, 1);
+                let (id, tx1, tx2) = &differing[0];
+                assert_eq!(*id, 1);
+                assert_eq!(tx1.differing_fields(tx2), vec![Field::Amount]);
             }
             _ => panic!("expected Mismatch"),
         }
     }
+
+    #[test]
+    fn test_compare_pending_wildcard() {
+        let csv1 = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,1234567890,PENDING,test\n\
+                2,DEPOSIT,0,42,1000,1234567890,PENDING,test\n";
+        let csv2 = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                2,DEPOSIT,0,42,1000,1234567890,FAILURE,test\n";
+        let opts = CompareOptions {
+            pending_wildcard: true,
+        };
+        let result = compare_with::<CsvFormat, CsvFormat>(
+            &mut Cursor::new(csv1),
+            &mut Cursor::new(csv2),
+            &opts,
+        )
+        .unwrap();
+        assert!(matches!(result, CompareResult::Identical));
+
+        let result =
+            compare::<CsvFormat, CsvFormat>(&mut Cursor::new(csv1), &mut Cursor::new(csv2))
+                .unwrap();
+        assert!(
+            matches!(result, CompareResult::Mismatch { differing, .. } if differing.len() == 2)
+        );
+
+        let csv3 = csv2.replace("1000,1234567890,SUCCESS", "999,1234567890,SUCCESS");
+        let result = compare_with::<CsvFormat, CsvFormat>(
+            &mut Cursor::new(csv1),
+            &mut Cursor::new(csv3),
+            &opts,
+        )
+        .unwrap();
+        assert!(
+            matches!(result, CompareResult::Mismatch { differing, .. } if differing.len() == 1)
+        );
+    }
+
+    #[test]
+    fn test_extract_changes() {
+        let csv1 = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                2,TRANSFER,10,20,500,1234567891,PENDING,second\n\
+                3,WITHDRAWAL,42,0,100,1234567892,SUCCESS,gone\n";
+        let csv2 = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                4,DEPOSIT,0,7,250,1234567893,SUCCESS,added\n\
+                1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                2,TRANSFER,10,20,500,1234567891,SUCCESS,second\n";
+        let mut out = Vec::new();
+        extract_changes::<CsvFormat, CsvFormat, BinFormat>(
+            &mut Cursor::new(csv1),
+            &mut Cursor::new(csv2),
+            &mut out,
+        )
+        .unwrap();
+
+        let changes = BinFormat::read_all(&mut out.as_slice()).unwrap();
+        let all_two = CsvFormat::read_all(&mut Cursor::new(csv2)).unwrap();
+        assert_eq!(changes, vec![all_two[0].clone(), all_two[2].clone()]);
+    }
+
+    #[test]
+    fn test_read_segments_csv_then_binary() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+        let second = Transaction {
+            tx_id: 2,
+            ..expected_transaction()
+        };
+        let mut stream = csv.as_bytes().to_vec();
+        BinFormat::write_all(&mut stream, std::slice::from_ref(&second)).unwrap();
+        let bin_len = stream.len() - csv.len();
+
+        let segments = vec![(FormatKind::Csv, csv.len()), (FormatKind::Bin, bin_len)];
+        let transactions = read_segments(segments, &mut stream.as_slice()).unwrap();
+        assert_eq!(transactions, vec![expected_transaction(), second]);
+
+        let truncated = vec![(FormatKind::Csv, csv.len()), (FormatKind::Bin, bin_len + 1)];
+        assert!(matches!(
+            read_segments(truncated, &mut stream.as_slice()),
+            Err(BankFormatError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_partition_by_day() {
+        // 2023-11-14 22:13:20 UTC, then one and three hours later (the latter on 11-15).
+        let base = 1_700_000_000_000;
+        let txs: Vec<Transaction> = [(1, base), (2, base + 3_600_000), (3, base + 10_800_000)]
+            .into_iter()
+            .map(|(tx_id, timestamp)| Transaction {
+                tx_id,
+                timestamp,
+                ..expected_transaction()
+            })
+            .collect();
+        let mut input = Vec::new();
+        BinFormat::write_all(&mut input, &txs).unwrap();
+
+        // Each day's writer appends to its own entry of a shared map.
+        struct DayWriter(i64, Rc<RefCell<BTreeMap<i64, Vec<u8>>>>);
+        impl std::io::Write for DayWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.1.borrow_mut().entry(self.0).or_default().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let outputs = Rc::new(RefCell::new(BTreeMap::new()));
+        partition_by_day::<BinFormat, _, _>(
+            &mut input.as_slice(),
+            |day| DayWriter(day, Rc::clone(&outputs)),
+            FormatKind::Csv,
+        )
+        .unwrap();
+
+        let first_day = base / 86_400_000 * 86_400_000;
+        let outputs = outputs.borrow();
+        assert_eq!(outputs.len(), 2);
+        let read = |day: i64| CsvFormat::read_all(&mut Cursor::new(&outputs[&day])).unwrap();
+        assert_eq!(read(first_day), txs[..2].to_vec());
+        assert_eq!(read(first_day + 86_400_000), txs[2..].to_vec());
+    }
+
+    #[test]
+    fn test_compare_with_progress_cancel() {
+        let mut buf = Vec::new();
+        let records: Vec<Transaction> = (1..=10)
+            .map(|tx_id| Transaction {
+                tx_id,
+                ..expected_transaction()
+            })
+            .collect();
+        BinFormat::write_all(&mut buf, &records).unwrap();
+
+        let opts = CompareOptions::default();
+        let mut calls = 0;
+        let result = compare_with_progress::<BinFormat, BinFormat>(
+            &mut Cursor::new(&buf),
+            &mut Cursor::new(&buf),
+            &opts,
+            |progress| {
+                calls += 1;
+                progress.read == 4
+            },
+        )
+        .unwrap();
+        assert_eq!(calls, 4);
+        match result {
+            ProgressCompareResult::Cancelled(progress) => assert_eq!(
+                progress,
+                CompareProgress {
+                    read: 4,
+                    compared: 0,
+                    total: 0
+                }
+            ),
+            other => panic!("expected Cancelled, got {:?}", other),
+        }
+
+        let result = compare_with_progress::<BinFormat, BinFormat>(
+            &mut Cursor::new(&buf),
+            &mut Cursor::new(&buf),
+            &opts,
+            |progress| progress.compared == 3,
+        )
+        .unwrap();
+        match result {
+            ProgressCompareResult::Cancelled(progress) => assert_eq!(
+                progress,
+                CompareProgress {
+                    read: 20,
+                    compared: 3,
+                    total: 20
+                }
+            ),
+            other => panic!("expected Cancelled, got {:?}", other),
+        }
+
+        let mut last = None;
+        let result = compare_with_progress::<BinFormat, BinFormat>(
+            &mut Cursor::new(&buf),
+            &mut Cursor::new(&buf),
+            &opts,
+            |progress| {
+                last = Some(progress);
+                false
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            result,
+            ProgressCompareResult::Completed(CompareResult::Identical)
+        ));
+        assert_eq!(last.map(|p| (p.read, p.compared)), Some((20, 20)));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let tx = expected_transaction();
+        // pinned so the hash stays comparable with values stored by earlier builds
+        assert_eq!(tx.content_hash(&[]), 0x345a_f9ac_6ca4_80c1);
+
+        let ignore = [Field::Description];
+        let hash = tx.content_hash(&ignore);
+        assert_eq!(hash, expected_transaction().content_hash(&ignore));
+
+        let renamed = Transaction {
+            description: "other".to_string(),
+            ..tx.clone()
+        };
+        assert_eq!(renamed.content_hash(&ignore), hash);
+        assert_ne!(renamed.content_hash(&[]), tx.content_hash(&[]));
+
+        let changed = Transaction {
+            amount: tx.amount + 1,
+            ..tx.clone()
+        };
+        assert_ne!(changed.content_hash(&ignore), hash);
+    }
+
+    #[test]
+    fn test_read_all_strict_rejects_duplicate_ids() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                2,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                1,DEPOSIT,0,42,2000,1234567890,SUCCESS,again\n";
+        assert_eq!(CsvFormat::read_all(&mut csv.as_bytes()).unwrap().len(), 3);
+        match read_all_strict::<CsvFormat>(&mut csv.as_bytes()) {
+            Err(BankFormatError::Parse(msg)) => assert_eq!(msg, "duplicate tx_id: 1"),
+            other => panic!("expected duplicate error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_read_all_validated() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                7,DEPOSIT,5,42,1000,1234567890,SUCCESS,test\n";
+        assert_eq!(CsvFormat::read_all(&mut csv.as_bytes()).unwrap().len(), 2);
+        match read_all_validated::<CsvFormat>(&mut csv.as_bytes()) {
+            Err(BankFormatError::Validation(msg)) => {
+                assert_eq!(msg, "tx 7: DEPOSIT must have from_user_id == 0")
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_export_failures() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,300,FAILURE,late\n\
+                2,DEPOSIT,0,42,1000,100,SUCCESS,ok\n\
+                3,DEPOSIT,0,42,1000,200,FAILURE,early\n\
+                4,DEPOSIT,0,42,1000,50,PENDING,waiting\n";
+        let mut output = Vec::new();
+        export_failures::<CsvFormat, BinFormat>(&mut csv.as_bytes(), &mut output).unwrap();
+
+        let failures = BinFormat::read_all(&mut output.as_slice()).unwrap();
+        let ids: Vec<TxId> = failures.iter().map(|tx| tx.tx_id).collect();
+        assert_eq!(ids, vec![3, 1]);
+    }
+
+    #[test]
+    fn test_convert_with_filters_and_modifies() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,100,SUCCESS,a\n\
+                2,TRANSFER,42,7,250,200,SUCCESS,b\n\
+                3,TRANSFER,7,42,50,300,FAILURE,c\n";
+        let mut output = Vec::new();
+        convert_with::<CsvFormat, TxtFormat>(&mut csv.as_bytes(), &mut output, |mut tx| {
+            tx.description.make_ascii_uppercase();
+            (tx.tx_type == TxType::Transfer).then_some(tx)
+        })
+        .unwrap();
+        let kept = TxtFormat::read_all(&mut output.as_slice()).unwrap();
+        let kept: Vec<(TxId, &str)> = kept
+            .iter()
+            .map(|tx| (tx.tx_id, tx.description.as_str()))
+            .collect();
+        assert_eq!(kept, vec![(2, "B"), (3, "C")]);
+
+        let mut output = Vec::new();
+        convert_with::<CsvFormat, CsvFormat>(&mut csv.as_bytes(), &mut output, |_| None).unwrap();
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n"
+        );
+        assert!(
+            CsvFormat::read_all(&mut output.as_slice())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_convert_for_sharing() {
+        let records = vec![
+            expected_transaction(),
+            Transaction {
+                tx_id: 2,
+                tx_type: TxType::Transfer,
+                from_user_id: 42,
+                to_user_id: 7,
+                amount: 250,
+                description: "rent for flat 3".to_string(),
+                ..expected_transaction()
+            },
+        ];
+        let mut input = Vec::new();
+        CsvFormat::write_all(&mut input, &records).unwrap();
+
+        let opts = ShareOptions {
+            salt: "s3cret".to_string(),
+            redact_mode: transform::RedactMode::Empty,
+        };
+        let mut output = Vec::new();
+        convert_for_sharing::<CsvFormat, BinFormat>(&mut input.as_slice(), &mut output, &opts)
+            .unwrap();
+        let shared = BinFormat::read_all(&mut output.as_slice()).unwrap();
+
+        assert_eq!(shared[0].from_user_id, 0);
+        assert_ne!(shared[0].to_user_id, 42);
+        assert_eq!(shared[0].to_user_id, shared[1].from_user_id);
+        assert_ne!(shared[1].to_user_id, 7);
+        assert!(shared.iter().all(|tx| tx.description.is_empty()));
+        let amounts: Vec<i64> = shared.iter().map(|tx| tx.amount).collect();
+        assert_eq!(amounts, vec![1000, 250]);
+    }
+
+    #[cfg(all(feature = "serde", feature = "json"))]
+    #[test]
+    fn test_serde_json_roundtrip() {
+        let tx = expected_transaction();
+        let value = serde_json::to_value(&tx).unwrap();
+        assert_eq!(value["tx_type"], "DEPOSIT");
+        assert_eq!(value["status"], "SUCCESS");
+        let back: Transaction = serde_json::from_value(value).unwrap();
+        assert_eq!(back, tx);
+
+        let other = Transaction {
+            tx_type: TxType::Other("REFUND".into()),
+            ..expected_transaction()
+        };
+        let json = serde_json::to_string(&other).unwrap();
+        assert!(json.contains(r#""tx_type":"REFUND""#), "got {json}");
+        assert_eq!(serde_json::from_str::<Transaction>(&json).unwrap(), other);
+    }
+
+    #[test]
+    fn test_read_all_lenient_skips_bad_records() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                2,DEPOSIT,0,42,notanumber,1234567890,SUCCESS,test\n\
+                3,DEPOSIT,0,42\n\
+                4,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+        let (txs, skipped) = CsvFormat::read_all_lenient(&mut csv.as_bytes());
+        assert_eq!(
+            txs.iter().map(|tx| tx.tx_id).collect::<Vec<_>>(),
+            vec![1, 4]
+        );
+        let indices: Vec<usize> = skipped.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![1, 2]);
+        assert!(
+            matches!(&skipped[0].1, BankFormatError::ParseAt { field, .. } if field == "amount")
+        );
+
+        let mut txt = Vec::new();
+        TxtFormat::write_all(&mut txt, &txs).unwrap();
+        let txt = String::from_utf8(txt)
+            .unwrap()
+            .replacen("AMOUNT: 1000", "AMOUNT: x", 1);
+        let (parsed, skipped) = TxtFormat::read_all_lenient(&mut txt.as_bytes());
+        assert_eq!(parsed, vec![txs[1].clone()]);
+        assert!(matches!(
+            skipped[..],
+            [(0, BankFormatError::ParseAt { record: 1, .. })]
+        ));
+
+        let mut bin = Vec::new();
+        BinFormat::write_all(&mut bin, &txs).unwrap();
+        bin.truncate(bin.len() - 1);
+        let (parsed, skipped) = BinFormat::read_all_lenient(&mut bin.as_slice());
+        assert_eq!(parsed, vec![txs[0].clone()]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, 1);
+    }
+
+    #[test]
+    fn test_compare_many_three_sources() {
+        let tx = |tx_id| Transaction {
+            tx_id,
+            ..expected_transaction()
+        };
+        let csv = vec![tx(1), tx(2), tx(3)];
+        let bin = vec![tx(1), tx(3)];
+        let mut txt = vec![tx(1), tx(2), tx(3)];
+        txt[2].amount = 999;
+
+        let result = compare_many(vec![("csv", csv), ("bin", bin), ("txt", txt)]);
+        assert!(!result.is_identical());
+        assert_eq!(result.entries.len(), 3);
+
+        let incomplete: Vec<_> = result.incomplete().collect();
+        assert_eq!(incomplete.len(), 1);
+        assert_eq!(incomplete[0].tx_id, 2);
+        assert_eq!(incomplete[0].missing_in, vec!["bin".to_string()]);
+        let present: Vec<&str> = incomplete[0]
+            .records
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect();
+        assert_eq!(present, vec!["csv", "txt"]);
+
+        let conflicting: Vec<_> = result.conflicting().collect();
+        assert_eq!(conflicting.len(), 1);
+        assert_eq!(conflicting[0].tx_id, 3);
+        assert_eq!(conflicting[0].differing, vec![Field::Amount]);
+        assert_eq!(conflicting[0].records[2].0, "txt");
+        assert_eq!(conflicting[0].records[2].1.amount, 999);
+
+        let same = compare_many(vec![("a", vec![tx(1)]), ("b", vec![tx(1)])]);
+        assert!(same.is_identical());
+    }
 }
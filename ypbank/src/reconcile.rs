@@ -0,0 +1,123 @@
+//! Reconciliation of transaction sets that do not match one-to-one by id.
+use crate::{Transaction, TxId};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A bucket whose amounts do not reconcile between the two sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmatchedBucket<K> {
+    /// The bucket key.
+    pub key: K,
+    /// Sum of amounts on side A, widened so that no sum of `i64` amounts overflows.
+    pub total_a: i128,
+    /// Sum of amounts on side B, widened like `total_a`.
+    pub total_b: i128,
+    /// Ids of the side A records in this bucket.
+    pub ids_a: Vec<TxId>,
+    /// Ids of the side B records in this bucket.
+    pub ids_b: Vec<TxId>,
+}
+
+/// Reconcile split payments: records are grouped by `key` and, within each bucket,
+/// the amounts on side B must sum to the amount on side A.
+///
+/// Typically a bucket holds one A record and several smaller B records. A bucket
+/// reconciles when the two totals differ by at most `tolerance`; buckets present on
+/// only one side are compared against a total of zero. Unmatched buckets are returned
+/// in the order their keys are first seen (side A first, then side B).
+pub fn reconcile_sums<K: Hash + Eq + Clone>(
+    a: &[Transaction],
+    b: &[Transaction],
+    key: impl Fn(&Transaction) -> K,
+    tolerance: i64,
+) -> Vec<UnmatchedBucket<K>> {
+    let mut index: HashMap<K, usize> = HashMap::new();
+    let mut buckets: Vec<UnmatchedBucket<K>> = Vec::new();
+
+    let sides = a
+        .iter()
+        .map(|tx| (true, tx))
+        .chain(b.iter().map(|tx| (false, tx)));
+    for (is_a, tx) in sides {
+        let k = key(tx);
+        let i = *index.entry(k.clone()).or_insert_with(|| {
+            buckets.push(UnmatchedBucket {
+                key: k,
+                total_a: 0,
+                total_b: 0,
+                ids_a: Vec::new(),
+                ids_b: Vec::new(),
+            });
+            buckets.len() - 1
+        });
+
+        let bucket = &mut buckets[i];
+        if is_a {
+            bucket.total_a += i128::from(tx.amount);
+            bucket.ids_a.push(tx.tx_id);
+        } else {
+            bucket.total_b += i128::from(tx.amount);
+            bucket.ids_b.push(tx.tx_id);
+        }
+    }
+
+    buckets
+        .into_iter()
+        .filter(|bucket| (bucket.total_a - bucket.total_b).abs() > i128::from(tolerance))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Status, TxType};
+
+    fn deposit(tx_id: TxId, to_user_id: i64, amount: i64) -> Transaction {
+        Transaction {
+            tx_id,
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id,
+            amount,
+            timestamp: 1234567890,
+            status: Status::Success,
+            description: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reconcile_split_payment() {
+        let a = vec![deposit(1, 42, 1000), deposit(2, 7, 500)];
+        let b = vec![
+            deposit(10, 42, 300),
+            deposit(11, 42, 500),
+            deposit(12, 42, 199),
+            deposit(13, 7, 400),
+        ];
+
+        let unmatched = reconcile_sums(&a, &b, |tx| tx.to_user_id, 1);
+        assert_eq!(
+            unmatched,
+            vec![UnmatchedBucket {
+                key: 7,
+                total_a: 500,
+                total_b: 400,
+                ids_a: vec![2],
+                ids_b: vec![13],
+            }]
+        );
+
+        assert_eq!(reconcile_sums(&a, &b, |tx| tx.to_user_id, 0).len(), 2);
+    }
+
+    #[test]
+    fn test_reconcile_large_amounts() {
+        let a = vec![deposit(1, 42, i64::MAX), deposit(2, 42, i64::MAX)];
+        let b = vec![deposit(10, 42, i64::MIN), deposit(11, 42, i64::MIN)];
+
+        let unmatched = reconcile_sums(&a, &b, |tx| tx.to_user_id, i64::MAX);
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].total_a, 2 * i128::from(i64::MAX));
+        assert_eq!(unmatched[0].total_b, 2 * i128::from(i64::MIN));
+    }
+}
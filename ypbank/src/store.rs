@@ -0,0 +1,104 @@
+//! An in-memory set of transactions keyed by id, for incremental ingestion.
+use crate::error::BankFormatError;
+use crate::{BankFormat, Transaction, TxId};
+use std::collections::HashMap;
+
+/// Transactions with unique ids, kept in insertion order.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionStore {
+    transactions: Vec<Transaction>,
+    index: HashMap<TxId, usize>,
+}
+
+/// Counts reported by [`TransactionStore::import_new`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ImportStats {
+    /// Records added to the store.
+    pub new: usize,
+    /// Records whose id was already in the store (or earlier in the same input).
+    pub skipped: usize,
+}
+
+impl TransactionStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored transactions.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Whether the store holds no transactions.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Whether a transaction with `tx_id` is stored.
+    pub fn contains(&self, tx_id: TxId) -> bool {
+        self.index.contains_key(&tx_id)
+    }
+
+    /// The stored transaction with `tx_id`, if any.
+    pub fn get(&self, tx_id: TxId) -> Option<&Transaction> {
+        self.index.get(&tx_id).map(|&i| &self.transactions[i])
+    }
+
+    /// Add `tx` unless its id is already stored. Returns whether it was added.
+    pub fn insert(&mut self, tx: Transaction) -> bool {
+        if self.contains(tx.tx_id) {
+            return false;
+        }
+        self.index.insert(tx.tx_id, self.transactions.len());
+        self.transactions.push(tx);
+        true
+    }
+
+    /// The stored transactions in insertion order.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Read `r` in format `F` and add every record whose id is not stored yet.
+    ///
+    /// Records with a known id are skipped without comparing their contents, so
+    /// importing the same input again is a no-op. If the input fails to parse,
+    /// nothing is added.
+    pub fn import_new<F: BankFormat>(
+        &mut self,
+        r: &mut impl std::io::Read,
+    ) -> Result<ImportStats, BankFormatError> {
+        let mut stats = ImportStats::default();
+        for tx in F::read_all(r)? {
+            if self.insert(tx) {
+                stats.new += 1;
+            } else {
+                stats.skipped += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CsvFormat;
+
+    #[test]
+    fn test_import_new_is_idempotent() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                   2,TRANSFER,10,20,500,1234567891,PENDING,second\n";
+        let mut store = TransactionStore::new();
+
+        let first = store.import_new::<CsvFormat>(&mut csv.as_bytes()).unwrap();
+        assert_eq!(first, ImportStats { new: 2, skipped: 0 });
+        let second = store.import_new::<CsvFormat>(&mut csv.as_bytes()).unwrap();
+        assert_eq!(second, ImportStats { new: 0, skipped: 2 });
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(2).unwrap().description, "second");
+    }
+}
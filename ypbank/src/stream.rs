@@ -0,0 +1,112 @@
+//! Utilities for streaming transaction data.
+use crate::error::BankFormatError;
+use crate::{BankFormat, Transaction};
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::thread::JoinHandle;
+
+/// A reader adaptor that counts the bytes consumed from the inner reader.
+///
+/// The count is kept in a shared counter, so progress can be polled through
+/// [`CountingReader::counter`] while the reader itself is borrowed by `read_all`.
+#[derive(Debug)]
+pub struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> CountingReader<R> {
+    /// Wrap `inner`, starting the count at zero.
+    pub fn new(inner: R) -> Self {
+        CountingReader {
+            inner,
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Number of bytes consumed so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// A handle to the shared byte counter.
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.count)
+    }
+
+    /// Unwrap the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Parse `r` with format `F` on a new thread, sending each transaction to `sender`.
+///
+/// Records are sent as they are parsed with [`BankFormat::read_iter`], and a bounded
+/// [`SyncSender`] applies back-pressure: the reader blocks while the channel is full.
+/// A read error is sent as the last message. The thread stops early when the
+/// receiver is dropped, and the channel closes once the thread finishes.
+pub fn spawn_reader<F, R>(
+    r: R,
+    sender: SyncSender<Result<Transaction, BankFormatError>>,
+) -> JoinHandle<()>
+where
+    F: BankFormat,
+    R: Read + Send + 'static,
+{
+    std::thread::spawn(move || {
+        for tx in F::read_iter(r) {
+            if sender.send(tx).is_err() {
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BankFormat, CsvFormat};
+
+    #[test]
+    fn test_counting_reader_counts_full_input() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                   2,TRANSFER,10,20,500,1234567891,PENDING,second\n";
+        let mut reader = CountingReader::new(csv.as_bytes());
+        let counter = reader.counter();
+
+        let transactions = CsvFormat::read_all(&mut reader).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(reader.bytes_read(), csv.len() as u64);
+        assert_eq!(counter.load(Ordering::Relaxed), csv.len() as u64);
+    }
+
+    #[test]
+    fn test_spawn_reader_fills_bounded_channel() {
+        let csv = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                   1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n\
+                   2,TRANSFER,10,20,500,1234567891,PENDING,second\n\
+                   3,WITHDRAWAL,42,0,100,1234567892,SUCCESS,third\n";
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        let handle = spawn_reader::<CsvFormat, _>(std::io::Cursor::new(csv), sender);
+
+        let ids: Vec<u64> = receiver.iter().map(|tx| tx.unwrap().tx_id).collect();
+        handle.join().unwrap();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        spawn_reader::<CsvFormat, _>(std::io::Cursor::new("tx_id\nnot-a-row\n"), sender);
+        assert!(matches!(receiver.iter().last(), Some(Err(_))));
+    }
+}
@@ -0,0 +1,83 @@
+//! Assertion helpers for test suites that check produced transaction files.
+//!
+//! Available with the `testing` feature.
+use crate::{BankFormat, Field, Transaction};
+
+/// Assert that `produced` and `golden` decode (with format `F`) to equivalent records.
+///
+/// Records are compared in order, ignoring the fields listed in `ignore`
+/// (typically [`Field::Timestamp`] when it is set to "now").
+///
+/// # Panics
+/// Panics if either input fails to parse, or with a readable per-field diff
+/// if the records differ.
+pub fn assert_equivalent<F: BankFormat>(produced: &[u8], golden: &[u8], ignore: &[Field]) {
+    let produced = F::read_all(&mut &produced[..])
+        .unwrap_or_else(|e| panic!("failed to parse produced input: {}", e));
+    let golden = F::read_all(&mut &golden[..])
+        .unwrap_or_else(|e| panic!("failed to parse golden input: {}", e));
+
+    let diff = equivalence_diff(&produced, &golden, ignore);
+    if !diff.is_empty() {
+        panic!(
+            "produced records differ from golden records:\n{}",
+            diff.join("\n")
+        );
+    }
+}
+
+fn equivalence_diff(
+    produced: &[Transaction],
+    golden: &[Transaction],
+    ignore: &[Field],
+) -> Vec<String> {
+    let mut diff = Vec::new();
+    if produced.len() != golden.len() {
+        diff.push(format!(
+            "record count: produced {} != golden {}",
+            produced.len(),
+            golden.len()
+        ));
+    }
+
+    for (i, (p, g)) in produced.iter().zip(golden).enumerate() {
+        for field in p.differing_fields(g) {
+            if ignore.contains(&field) {
+                continue;
+            }
+            diff.push(format!(
+                "record {} (tx {}): {}: produced {:?} != golden {:?}",
+                i,
+                g.tx_id,
+                field,
+                p.field_string(field),
+                g.field_string(field)
+            ));
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CsvFormat;
+
+    const GOLDEN: &str = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                          1,DEPOSIT,0,42,1000,1234567890,SUCCESS,test\n";
+
+    #[test]
+    fn test_assert_equivalent_ignores_timestamp() {
+        let produced = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                        1,DEPOSIT,0,42,1000,1700000000000,SUCCESS,test\n";
+        assert_equivalent::<CsvFormat>(produced.as_bytes(), GOLDEN.as_bytes(), &[Field::Timestamp]);
+    }
+
+    #[test]
+    #[should_panic(expected = "amount: produced \"9999\" != golden \"1000\"")]
+    fn test_assert_equivalent_reports_diff() {
+        let produced = "tx_id,tx_type,from_user_id,to_user_id,amount,timestamp,status,description\n\
+                        1,DEPOSIT,0,42,9999,1700000000000,SUCCESS,test\n";
+        assert_equivalent::<CsvFormat>(produced.as_bytes(), GOLDEN.as_bytes(), &[Field::Timestamp]);
+    }
+}
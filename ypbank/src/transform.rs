@@ -0,0 +1,349 @@
+//! Transformations that rewrite sets of transaction records.
+use crate::{FNV_OFFSET, Field, Transaction, TxId, TxType, fnv1a};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A key to order transactions by.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SortKey {
+    /// Order by [`Transaction::tx_id`].
+    TxId,
+    /// Order by [`Transaction::timestamp`].
+    Timestamp,
+    /// Order by [`Transaction::amount`].
+    Amount,
+}
+
+impl SortKey {
+    /// Compare two transactions by this key only.
+    pub fn compare(&self, a: &Transaction, b: &Transaction) -> Ordering {
+        match self {
+            SortKey::TxId => a.tx_id.cmp(&b.tx_id),
+            SortKey::Timestamp => a.timestamp.cmp(&b.timestamp),
+            SortKey::Amount => a.amount.cmp(&b.amount),
+        }
+    }
+}
+
+/// Stably sort transactions by `by`, so records with equal keys keep their input order.
+///
+/// Ties on [`SortKey::Timestamp`] are broken by `tx_id`, so the result does not depend
+/// on the input order for records with distinct ids.
+pub fn sort_transactions(txs: &mut [Transaction], by: SortKey) {
+    txs.sort_by(|a, b| {
+        let order = by.compare(a, b);
+        match by {
+            SortKey::Timestamp => order.then_with(|| a.tx_id.cmp(&b.tx_id)),
+            SortKey::TxId | SortKey::Amount => order,
+        }
+    });
+}
+
+/// Replace every `TRANSFER` with a `WITHDRAWAL` from the sender followed by a
+/// `DEPOSIT` to the recipient.
+///
+/// Each generated record gets a fresh id from `next_id`; all other fields are copied
+/// from the original transfer. Non-transfer records are passed through unchanged and
+/// the relative order of records is preserved.
+pub fn explode_transfers(
+    txs: Vec<Transaction>,
+    mut next_id: impl FnMut() -> TxId,
+) -> Vec<Transaction> {
+    let mut out = Vec::with_capacity(txs.len());
+    for tx in txs {
+        if tx.tx_type != TxType::Transfer {
+            out.push(tx);
+            continue;
+        }
+
+        let withdrawal = Transaction {
+            tx_id: next_id(),
+            tx_type: TxType::Withdrawal,
+            to_user_id: 0,
+            ..tx.clone()
+        };
+        let deposit = Transaction {
+            tx_id: next_id(),
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            ..tx
+        };
+        out.push(withdrawal);
+        out.push(deposit);
+    }
+    out
+}
+
+/// Renumber transactions to consecutive ids.
+///
+/// Records are stably sorted by `order`, then assigned ids `start`, `start + 1`, ...
+/// in that order.
+pub fn resequence(txs: &mut [Transaction], start: TxId, order: SortKey) {
+    txs.sort_by(|a, b| order.compare(a, b));
+    for (tx, id) in txs.iter_mut().zip(start..) {
+        tx.tx_id = id;
+    }
+}
+
+/// Trim each description and collapse internal runs of whitespace to a single space.
+///
+/// This is lossy: the original spacing, tabs and line breaks can not be restored.
+pub fn normalize_whitespace(txs: &mut [Transaction]) {
+    for tx in txs {
+        tx.description = tx
+            .description
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+}
+
+/// How [`dedup_by_id`] combines the descriptions of duplicate records.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DescriptionMerge {
+    /// Keep the description of the first record.
+    PreferFirst,
+    /// Keep the longest description (by characters), the first one on ties.
+    PreferLonger,
+    /// Join the distinct descriptions in input order with the given separator.
+    Concatenate(String),
+}
+
+/// Collapse records that share a `tx_id` and differ at most in their description.
+///
+/// The merged record takes the position of the first duplicate and a description
+/// combined according to `policy`. Records with the same id but other differing
+/// fields are real conflicts and are all kept. Useful on the output of
+/// [`crate::merge_sorted`] when sources overlap.
+pub fn dedup_by_id(txs: Vec<Transaction>, policy: &DescriptionMerge) -> Vec<Transaction> {
+    let mut out: Vec<Transaction> = Vec::with_capacity(txs.len());
+    let mut by_id: HashMap<TxId, Vec<usize>> = HashMap::new();
+
+    for tx in txs {
+        let candidates = by_id.entry(tx.tx_id).or_default();
+        let same = candidates.iter().copied().find(|&i| {
+            out[i]
+                .differing_fields(&tx)
+                .iter()
+                .all(|field| *field == Field::Description)
+        });
+        let Some(i) = same else {
+            candidates.push(out.len());
+            out.push(tx);
+            continue;
+        };
+
+        let kept = &mut out[i];
+        match policy {
+            DescriptionMerge::PreferFirst => {}
+            DescriptionMerge::PreferLonger => {
+                if tx.description.chars().count() > kept.description.chars().count() {
+                    kept.description = tx.description;
+                }
+            }
+            DescriptionMerge::Concatenate(sep) => {
+                if !kept
+                    .description
+                    .split(sep.as_str())
+                    .any(|d| d == tx.description)
+                {
+                    kept.description.push_str(sep);
+                    kept.description.push_str(&tx.description);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Replace every user id except the system id `0` with a salted pseudonym.
+///
+/// The pseudonym is a positive id derived from `salt` and the original id, so the
+/// same user maps to the same pseudonym across records and runs. This is a stable
+/// hash, not encryption: anyone who knows the salt can link ids back by brute force.
+pub fn anonymize_users(txs: &mut [Transaction], salt: &str) {
+    let pseudonym = |id: i64| {
+        if id == 0 {
+            return 0;
+        }
+        let hash = fnv1a(fnv1a(FNV_OFFSET, salt.as_bytes()), &id.to_le_bytes());
+        match (hash & i64::MAX as u64) as i64 {
+            0 => 1,
+            id => id,
+        }
+    };
+    for tx in txs {
+        tx.from_user_id = pseudonym(tx.from_user_id);
+        tx.to_user_id = pseudonym(tx.to_user_id);
+    }
+}
+
+/// How [`redact_descriptions`] rewrites a description.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum RedactMode {
+    /// Replace the description with an empty string.
+    #[default]
+    Empty,
+    /// Replace every character with `*`, keeping the length visible.
+    Mask,
+}
+
+/// Redact every description according to `mode`.
+pub fn redact_descriptions(txs: &mut [Transaction], mode: RedactMode) {
+    for tx in txs {
+        tx.description = match mode {
+            RedactMode::Empty => String::new(),
+            RedactMode::Mask => "*".repeat(tx.description.chars().count()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    fn transfer() -> Transaction {
+        Transaction {
+            tx_id: 7,
+            tx_type: TxType::Transfer,
+            from_user_id: 10,
+            to_user_id: 20,
+            amount: 500,
+            timestamp: 1234567890,
+            status: Status::Success,
+            description: "rent".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_explode_transfers() {
+        let deposit = Transaction {
+            tx_id: 1,
+            tx_type: TxType::Deposit,
+            from_user_id: 0,
+            to_user_id: 10,
+            ..transfer()
+        };
+        let mut ids = 100..;
+        let out = explode_transfers(vec![deposit.clone(), transfer()], || ids.next().unwrap());
+
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0], deposit);
+        assert_eq!(
+            out[1],
+            Transaction {
+                tx_id: 100,
+                tx_type: TxType::Withdrawal,
+                to_user_id: 0,
+                ..transfer()
+            }
+        );
+        assert_eq!(
+            out[2],
+            Transaction {
+                tx_id: 101,
+                tx_type: TxType::Deposit,
+                from_user_id: 0,
+                ..transfer()
+            }
+        );
+    }
+
+    #[test]
+    fn test_resequence_by_timestamp() {
+        let mut txs: Vec<Transaction> = [(42, 300), (7, 100), (99, 200)]
+            .into_iter()
+            .map(|(tx_id, timestamp)| Transaction {
+                tx_id,
+                timestamp,
+                ..transfer()
+            })
+            .collect();
+
+        resequence(&mut txs, 1000, SortKey::Timestamp);
+        let ids_and_times: Vec<(TxId, i64)> =
+            txs.iter().map(|tx| (tx.tx_id, tx.timestamp)).collect();
+        assert_eq!(ids_and_times, vec![(1000, 100), (1001, 200), (1002, 300)]);
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let mut txs = vec![transfer(), transfer()];
+        txs[0].description = "pay   rent \n".to_string();
+        txs[1].description = "\t ".to_string();
+
+        normalize_whitespace(&mut txs);
+        assert_eq!(txs[0].description, "pay rent");
+        assert_eq!(txs[1].description, "");
+    }
+
+    #[test]
+    fn test_anonymize_and_redact() {
+        let mut txs = vec![transfer(), transfer()];
+        txs[1].from_user_id = 0;
+        anonymize_users(&mut txs, "salt");
+        redact_descriptions(&mut txs, RedactMode::Mask);
+
+        assert!(txs[0].from_user_id > 0 && txs[0].from_user_id != 10);
+        assert_eq!(txs[0].to_user_id, txs[1].to_user_id);
+        assert_eq!(txs[1].from_user_id, 0);
+        assert_eq!(txs[0].description, "****");
+
+        let mut other_salt = vec![transfer()];
+        anonymize_users(&mut other_salt, "pepper");
+        assert_ne!(other_salt[0].from_user_id, txs[0].from_user_id);
+    }
+
+    #[test]
+    fn test_dedup_by_id_description_policies() {
+        let short = transfer();
+        let long = Transaction {
+            description: "rent for March".to_string(),
+            ..transfer()
+        };
+        let conflict = Transaction {
+            amount: 999,
+            ..transfer()
+        };
+        let txs = vec![short.clone(), long.clone(), conflict.clone(), short.clone()];
+
+        let dedup = |policy| dedup_by_id(txs.clone(), &policy);
+        assert_eq!(
+            dedup(DescriptionMerge::PreferFirst),
+            vec![short.clone(), conflict.clone()]
+        );
+        assert_eq!(
+            dedup(DescriptionMerge::PreferLonger),
+            vec![long.clone(), conflict.clone()]
+        );
+        let concatenated = dedup(DescriptionMerge::Concatenate(" | ".to_string()));
+        assert_eq!(concatenated[0].description, "rent | rent for March");
+        assert_eq!(concatenated[1], conflict);
+    }
+
+    #[test]
+    fn test_sort_transactions() {
+        let mut txs: Vec<Transaction> = [(3, 200, 50), (1, 200, 10), (2, 100, 50)]
+            .into_iter()
+            .map(|(tx_id, timestamp, amount)| Transaction {
+                tx_id,
+                timestamp,
+                amount,
+                ..transfer()
+            })
+            .collect();
+        let ids = |txs: &[Transaction]| txs.iter().map(|tx| tx.tx_id).collect::<Vec<_>>();
+
+        sort_transactions(&mut txs, SortKey::Timestamp);
+        assert_eq!(ids(&txs), vec![2, 1, 3]);
+        sort_transactions(&mut txs, SortKey::Amount);
+        assert_eq!(ids(&txs), vec![1, 2, 3]);
+        sort_transactions(&mut txs, SortKey::TxId);
+        assert_eq!(ids(&txs), vec![1, 2, 3]);
+
+        txs.swap(1, 2);
+        sort_transactions(&mut txs, SortKey::Amount);
+        assert_eq!(ids(&txs), vec![1, 3, 2], "equal amounts keep input order");
+    }
+}
@@ -1,104 +1,229 @@
-use crate::error::BankFormatError;
-use crate::{BankFormat, Status, Transaction, TxType};
+use crate::error::{BankFormatError, FieldError};
+use crate::{BankFormat, Field, Status, Transaction, TxType, collect_lenient};
 use std::collections::HashMap;
 use std::io::{BufRead, Write};
 
 pub struct TxtFormat;
 
-impl BankFormat for TxtFormat {
-    fn read_all<R: std::io::Read>(r: &mut R) -> Result<Vec<Transaction>, BankFormatError> {
-        let reader = std::io::BufReader::new(r);
-        let mut transactions = Vec::new();
-        let mut current: HashMap<String, String> = HashMap::new();
-
-        for line in reader.lines() {
-            let line = line.map_err(BankFormatError::Io)?;
-            let line = line.trim().to_string();
-
-            if line.starts_with('#') {
-                if !current.is_empty() {
-                    transactions.push(TxtFormat::parse_map(&current)?);
-                    current.clear();
-                }
-            } else if let Some((key, value)) = line.split_once(':') {
-                current.insert(
-                    key.trim().to_string(),
-                    value.trim().trim_matches('"').to_string(),
-                );
-            }
-        }
+/// Options controlling how [`TxtFormat`] parses its input.
+#[derive(Debug, Clone, Default)]
+pub struct TxtOptions {
+    /// Preserve unknown `TX_TYPE`/`STATUS` values as [`TxType::Other`]/[`Status::Other`]
+    /// instead of failing. Defaults to `false`.
+    pub lenient_enums: bool,
+}
 
-        if !current.is_empty() {
-            transactions.push(TxtFormat::parse_map(&current)?);
-        }
+impl BankFormat for TxtFormat {
+    fn read_iter<R: std::io::Read>(
+        r: R,
+    ) -> impl Iterator<Item = Result<Transaction, BankFormatError>> {
+        TxtFormat::read_iter_with(r, TxtOptions::default())
+    }
 
-        Ok(transactions)
+    fn read_all_lenient<R: std::io::Read>(
+        r: &mut R,
+    ) -> (Vec<Transaction>, Vec<(usize, BankFormatError)>) {
+        let mut reader = TxtFormat::read_iter_with(r, TxtOptions::default());
+        collect_lenient(|| reader.next_record())
     }
 
     fn write_all<W: Write>(w: &mut W, records: &[Transaction]) -> Result<(), BankFormatError> {
         for (i, tx) in records.iter().enumerate() {
-            writeln!(w, "# Record {} ({})", i + 1, tx.tx_type).map_err(BankFormatError::Io)?;
-            writeln!(w, "TX_ID: {}", tx.tx_id).map_err(BankFormatError::Io)?;
-            writeln!(w, "TX_TYPE: {}", tx.tx_type).map_err(BankFormatError::Io)?;
-            writeln!(w, "FROM_USER_ID: {}", tx.from_user_id).map_err(BankFormatError::Io)?;
-            writeln!(w, "TO_USER_ID: {}", tx.to_user_id).map_err(BankFormatError::Io)?;
-            writeln!(w, "AMOUNT: {}", tx.amount).map_err(BankFormatError::Io)?;
-            writeln!(w, "TIMESTAMP: {}", tx.timestamp).map_err(BankFormatError::Io)?;
-            writeln!(w, "STATUS: {}", tx.status).map_err(BankFormatError::Io)?;
-            writeln!(w, "DESCRIPTION: \"{}\"", tx.description).map_err(BankFormatError::Io)?;
-            writeln!(w).map_err(BankFormatError::Io)?;
+            write_record(w, i + 1, tx)?;
         }
         Ok(())
     }
 }
 
+/// Write one record block, headed by the 1-based record `number`.
+pub(crate) fn write_record<W: Write>(
+    w: &mut W,
+    number: usize,
+    tx: &Transaction,
+) -> Result<(), BankFormatError> {
+    writeln!(w, "# Record {} ({})", number, tx.tx_type).map_err(BankFormatError::Io)?;
+    writeln!(w, "TX_ID: {}", tx.tx_id).map_err(BankFormatError::Io)?;
+    writeln!(w, "TX_TYPE: {}", tx.tx_type).map_err(BankFormatError::Io)?;
+    writeln!(w, "FROM_USER_ID: {}", tx.from_user_id).map_err(BankFormatError::Io)?;
+    writeln!(w, "TO_USER_ID: {}", tx.to_user_id).map_err(BankFormatError::Io)?;
+    writeln!(w, "AMOUNT: {}", tx.amount).map_err(BankFormatError::Io)?;
+    writeln!(w, "TIMESTAMP: {}", tx.timestamp).map_err(BankFormatError::Io)?;
+    writeln!(w, "STATUS: {}", tx.status).map_err(BankFormatError::Io)?;
+    writeln!(w, "DESCRIPTION: \"{}\"", escape(&tx.description)).map_err(BankFormatError::Io)?;
+    writeln!(w).map_err(BankFormatError::Io)?;
+    Ok(())
+}
+
 impl TxtFormat {
-    fn parse_map(map: &HashMap<String, String>) -> Result<Transaction, BankFormatError> {
-        let get = |key: &str| -> Result<&str, BankFormatError> {
-            map.get(key)
-                .map(|s| s.as_str())
-                .ok_or_else(|| BankFormatError::Parse(format!("missing field: {key}")))
+    /// Read all transactions using the given [`TxtOptions`].
+    pub fn read_all_with<R: std::io::Read>(
+        r: &mut R,
+        opts: &TxtOptions,
+    ) -> Result<Vec<Transaction>, BankFormatError> {
+        TxtFormat::read_iter_with(r, opts.clone()).collect()
+    }
+
+    /// Stream transactions one record at a time using the given [`TxtOptions`].
+    pub fn read_iter_with<R: std::io::Read>(r: R, opts: TxtOptions) -> TxtReader<R> {
+        TxtReader {
+            lines: std::io::BufReader::new(r).lines(),
+            opts,
+            current: HashMap::new(),
+            line_no: 0,
+            header_line: None,
+            start_line: 0,
+            records: 0,
+            done: false,
+        }
+    }
+
+    fn parse_map(
+        map: &HashMap<String, String>,
+        opts: &TxtOptions,
+    ) -> Result<Transaction, FieldError> {
+        // keys are the uppercase field names, e.g. `TX_ID`
+        let get = |field: Field| -> Result<&str, FieldError> {
+            let key = field.as_str().to_uppercase();
+            map.get(&key).map(|s| s.as_str()).ok_or(FieldError {
+                field,
+                detail: format!("missing field: {key}"),
+            })
+        };
+        let number = |field: Field| -> Result<i64, FieldError> {
+            let value = get(field)?;
+            value
+                .parse()
+                .map_err(|_| FieldError::invalid(field, &field.as_str().to_uppercase(), value))
         };
 
+        let tx_id = get(Field::TxId)?;
         Ok(Transaction {
-            tx_id: get("TX_ID")?
-                .parse()
-                .map_err(|_| BankFormatError::Parse("TX_ID".into()))?,
-            tx_type: TxtFormat::parse_tx_type(get("TX_TYPE")?)?,
-            from_user_id: get("FROM_USER_ID")?
-                .parse()
-                .map_err(|_| BankFormatError::Parse("FROM_USER_ID".into()))?,
-            to_user_id: get("TO_USER_ID")?
-                .parse()
-                .map_err(|_| BankFormatError::Parse("TO_USER_ID".into()))?,
-            amount: get("AMOUNT")?
-                .parse()
-                .map_err(|_| BankFormatError::Parse("AMOUNT".into()))?,
-            timestamp: get("TIMESTAMP")?
+            tx_id: tx_id
                 .parse()
-                .map_err(|_| BankFormatError::Parse("TIMESTAMP".into()))?,
-            status: TxtFormat::parse_status(get("STATUS")?)?,
-            description: get("DESCRIPTION")?.to_string(),
+                .map_err(|_| FieldError::invalid(Field::TxId, "TX_ID", tx_id))?,
+            tx_type: TxType::from_name(get(Field::TxType)?, opts.lenient_enums)
+                .map_err(|e| FieldError::from_error(Field::TxType, e))?,
+            from_user_id: number(Field::FromUserId)?,
+            to_user_id: number(Field::ToUserId)?,
+            amount: number(Field::Amount)?,
+            timestamp: number(Field::Timestamp)?,
+            status: Status::from_name(get(Field::Status)?, opts.lenient_enums)
+                .map_err(|e| FieldError::from_error(Field::Status, e))?,
+            description: get(Field::Description)?.to_string(),
         })
     }
+}
+
+/// Iterator over the records of a text stream, returned by [`TxtFormat::read_iter_with`].
+///
+/// An error is yielded in place of the record that caused it and ends the iteration.
+#[derive(Debug)]
+pub struct TxtReader<R> {
+    lines: std::io::Lines<std::io::BufReader<R>>,
+    opts: TxtOptions,
+    current: HashMap<String, String>,
+    /// Number of the last line read.
+    line_no: u64,
+    /// Line of a `#` header not yet followed by a field.
+    header_line: Option<u64>,
+    /// Line on which the record in `current` starts.
+    start_line: u64,
+    /// Number of records parsed so far.
+    records: usize,
+    done: bool,
+}
+
+impl<R: std::io::Read> TxtReader<R> {
+    fn next_record(&mut self) -> Result<Option<Transaction>, BankFormatError> {
+        while let Some(line) = self.lines.next() {
+            let line = line.map_err(BankFormatError::Io)?;
+            let line = line.trim();
+            self.line_no += 1;
+
+            if line.starts_with('#') {
+                let finished = self.finish_record();
+                self.header_line = Some(self.line_no);
+                if !matches!(finished, Ok(None)) {
+                    return finished;
+                }
+            } else if let Some((key, value)) = line.split_once(':') {
+                if self.current.is_empty() {
+                    self.start_line = self.header_line.take().unwrap_or(self.line_no);
+                }
+                let value = value.trim();
+                let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                    Some(quoted) => unescape(quoted),
+                    None => value.to_string(),
+                };
+                self.current.insert(key.trim().to_string(), value);
+            }
+        }
+        self.finish_record()
+    }
 
-    fn parse_tx_type(s: &str) -> Result<TxType, BankFormatError> {
-        match s {
-            "DEPOSIT" => Ok(TxType::Deposit),
-            "TRANSFER" => Ok(TxType::Transfer),
-            "WITHDRAWAL" => Ok(TxType::Withdrawal),
-            other => Err(BankFormatError::Parse(format!("unknown tx_type: {other}"))),
+    /// Parse and clear the accumulated record, if any.
+    fn finish_record(&mut self) -> Result<Option<Transaction>, BankFormatError> {
+        if self.current.is_empty() {
+            return Ok(None);
         }
+        self.records += 1;
+        let map = std::mem::take(&mut self.current);
+        TxtFormat::parse_map(&map, &self.opts)
+            .map(Some)
+            .map_err(|e| e.at(self.records, Some(self.start_line)))
     }
+}
+
+impl<R: std::io::Read> Iterator for TxtReader<R> {
+    type Item = Result<Transaction, BankFormatError>;
 
-    fn parse_status(s: &str) -> Result<Status, BankFormatError> {
-        match s {
-            "SUCCESS" => Ok(Status::Success),
-            "FAILURE" => Ok(Status::Failure),
-            "PENDING" => Ok(Status::Pending),
-            other => Err(BankFormatError::Parse(format!("unknown status: {other}"))),
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_record().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// Escape `"`, `\` and line breaks so that `s` fits on a single quoted line.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse [`escape`]. Unknown escape sequences are kept verbatim.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
         }
     }
+    out
 }
 
 #[cfg(test)]
@@ -217,11 +342,60 @@ mod tests {
         for (bad_txt, expected_msg) in cases {
             let mut cursor = Cursor::new(bad_txt);
             match TxtFormat::read_all(&mut cursor) {
-                Err(BankFormatError::Parse(msg)) => {
+                Err(BankFormatError::ParseAt { detail: msg, .. }) => {
                     assert!(msg.contains(expected_msg), "got: {}", msg);
                 }
-                other => panic!("expected Parse error, got {:?}", other),
+                other => panic!("expected ParseAt error, got {:?}", other),
             }
         }
     }
+
+    #[test]
+    fn test_lenient_enums_roundtrip() {
+        let mut tx = expected_transaction();
+        tx.tx_type = TxType::Other("REFUND".into());
+        tx.status = Status::Other("REVERSED".into());
+        let mut buf = Vec::new();
+        TxtFormat::write_all(&mut buf, std::slice::from_ref(&tx)).unwrap();
+
+        let opts = TxtOptions {
+            lenient_enums: true,
+        };
+        let parsed = TxtFormat::read_all_with(&mut Cursor::new(&buf), &opts).unwrap();
+        assert_eq!(parsed, vec![tx]);
+        assert!(TxtFormat::read_all(&mut Cursor::new(&buf)).is_err());
+    }
+
+    #[test]
+    fn test_read_iter_streams_records() {
+        let input = format!("{}# Record 2\nTX_ID: oops\n", make_valid_txt());
+        let mut iter = TxtFormat::read_iter(input.as_bytes());
+        assert_eq!(iter.next().unwrap().unwrap(), expected_transaction());
+        let line = make_valid_txt().lines().count() as u64 + 1;
+        match iter.next() {
+            Some(Err(e @ BankFormatError::ParseAt { record: 2, .. })) => assert_eq!(
+                e.to_string(),
+                format!("Parse error: record 2 (line {line}): invalid TX_ID 'oops'")
+            ),
+            other => panic!("expected ParseAt error, got {:?}", other),
+        }
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_description_escaping_roundtrip() {
+        let tx = Transaction {
+            description: "paid for \"lunch\"\nthanks C:\\tmp\r\n".to_string(),
+            ..expected_transaction()
+        };
+        let mut buf = Vec::new();
+        TxtFormat::write_all(&mut buf, std::slice::from_ref(&tx)).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains(r#"DESCRIPTION: "paid for \"lunch\"\nthanks C:\\tmp\r\n""#));
+        assert_eq!(TxtFormat::read_all(&mut text.as_bytes()).unwrap(), vec![tx]);
+
+        let unknown = make_valid_txt().replace("\"test\"", r#""a\tb""#);
+        let parsed = TxtFormat::read_all(&mut unknown.as_bytes()).unwrap();
+        assert_eq!(parsed[0].description, r"a\tb");
+    }
 }
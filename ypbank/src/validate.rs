@@ -0,0 +1,301 @@
+//! Semantic checks over parsed transaction records.
+use crate::error::BankFormatError;
+use crate::{Transaction, TxId, TxType};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A plausible range for millisecond timestamps: 2000-01-01 up to (excluding)
+/// 2100-01-01 UTC. Second-scale timestamps of the same era fall far below it.
+pub const DEFAULT_PLAUSIBLE_TIMESTAMP_RANGE: (i64, i64) = (946_684_800_000, 4_102_444_800_000);
+
+/// Rule of a `DEPOSIT` that does not come from the system user.
+pub(crate) const DEPOSIT_FROM_SYSTEM: &str = "DEPOSIT must have from_user_id == 0";
+/// Rule of a `WITHDRAWAL` that does not go to the system user.
+pub(crate) const WITHDRAWAL_TO_SYSTEM: &str = "WITHDRAWAL must have to_user_id == 0";
+/// Rule of a `TRANSFER` sent by the system user.
+const TRANSFER_FROM_USER: &str = "TRANSFER must not have from_user_id == 0";
+/// Rule of a `TRANSFER` received by the system user.
+const TRANSFER_TO_USER: &str = "TRANSFER must not have to_user_id == 0";
+
+/// A record that breaks a rule checked by [`Transaction::validate`], [`validate_all`]
+/// or [`validate`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationError {
+    /// Id of the offending record.
+    pub tx_id: TxId,
+    /// The rule that failed, e.g. `DEPOSIT must have from_user_id == 0`.
+    pub rule: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tx {}: {}", self.tx_id, self.rule)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for BankFormatError {
+    fn from(e: ValidationError) -> Self {
+        BankFormatError::Validation(e.to_string())
+    }
+}
+
+/// Check every record with [`Transaction::validate`] and reject repeated `tx_id`s,
+/// failing on the first violation in input order.
+pub fn validate_all(txs: &[Transaction]) -> Result<(), ValidationError> {
+    let mut seen: HashSet<TxId> = HashSet::with_capacity(txs.len());
+    first_violation(txs, |tx| match tx.validate() {
+        Err(e) => Some(e.rule),
+        Ok(()) if !seen.insert(tx.tx_id) => Some("duplicate tx_id".to_string()),
+        Ok(()) => None,
+    })
+}
+
+/// Fail with the first rule that `check` reports, in input order.
+fn first_violation(
+    txs: &[Transaction],
+    mut check: impl FnMut(&Transaction) -> Option<String>,
+) -> Result<(), ValidationError> {
+    for tx in txs {
+        if let Some(rule) = check(tx) {
+            return Err(ValidationError {
+                tx_id: tx.tx_id,
+                rule,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The rules of a `TRANSFER` that involves the system user, in field order.
+fn transfer_party_violations(tx: &Transaction) -> impl Iterator<Item = &'static str> {
+    let transfer = tx.tx_type == TxType::Transfer;
+    [
+        (transfer && tx.from_user_id == 0).then_some(TRANSFER_FROM_USER),
+        (transfer && tx.to_user_id == 0).then_some(TRANSFER_TO_USER),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Rules applied by [`validate`]. The default accepts everything the formats parse.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationOptions {
+    /// Reject a `TRANSFER` whose sender or recipient is `0`.
+    ///
+    /// `0` is the system sentinel used by deposits and withdrawals, so a transfer
+    /// touching it usually means the sentinel leaked in as a real user id.
+    /// Defaults to `false`.
+    pub reject_zero_transfer_party: bool,
+    /// Reject a `TRANSFER` whose sender and recipient are the same user.
+    /// Defaults to `false`. See [`crate::analysis::find_self_transfers`].
+    pub reject_self_transfers: bool,
+    /// Reject a timestamp outside `start..end` (epoch milliseconds), typically one
+    /// written in seconds by mistake. Defaults to `None` (no check); see
+    /// [`DEFAULT_PLAUSIBLE_TIMESTAMP_RANGE`] for a sensible range.
+    pub plausible_timestamp_range: Option<(i64, i64)>,
+}
+
+/// Check `txs` against the enabled rules, failing on the first violation
+/// with [`BankFormatError::Validation`] built from a [`ValidationError`].
+pub fn validate(txs: &[Transaction], opts: &ValidationOptions) -> Result<(), BankFormatError> {
+    first_violation(txs, |tx| {
+        if opts.reject_zero_transfer_party
+            && let Some(rule) = transfer_party_violations(tx).next()
+        {
+            return Some(rule.to_string());
+        }
+        if opts.reject_self_transfers
+            && tx.tx_type == TxType::Transfer
+            && tx.from_user_id == tx.to_user_id
+        {
+            return Some("TRANSFER must have from_user_id != to_user_id".to_string());
+        }
+        match opts.plausible_timestamp_range {
+            Some((start, end)) if !(start..end).contains(&tx.timestamp) => Some(format!(
+                "timestamp {} must be in the plausible range {}..{}",
+                tx.timestamp, start, end
+            )),
+            _ => None,
+        }
+    })?;
+    Ok(())
+}
+
+/// List every record that breaks the system user conventions, with the rule it breaks.
+///
+/// A `DEPOSIT` must come from user `0`, a `WITHDRAWAL` must go to user `0`, and a
+/// `TRANSFER` must involve two real (non-zero) users. The rules read as in
+/// [`ValidationError`]. A record may appear more than once if it breaks several rules.
+/// Unlike [`validate`] this never fails.
+pub fn convention_violations(txs: &[Transaction]) -> Vec<(TxId, String)> {
+    let mut violations = Vec::new();
+    for tx in txs {
+        let rule = match tx.tx_type {
+            TxType::Deposit if tx.from_user_id != 0 => Some(DEPOSIT_FROM_SYSTEM),
+            TxType::Withdrawal if tx.to_user_id != 0 => Some(WITHDRAWAL_TO_SYSTEM),
+            _ => None,
+        };
+        violations.extend(
+            rule.into_iter()
+                .chain(transfer_party_violations(tx))
+                .map(|rule| (tx.tx_id, rule.to_string())),
+        );
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    fn transfer(from_user_id: i64, to_user_id: i64) -> Transaction {
+        Transaction {
+            tx_id: 7,
+            tx_type: TxType::Transfer,
+            from_user_id,
+            to_user_id,
+            amount: 500,
+            timestamp: 1234567890,
+            status: Status::Success,
+            description: "rent".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_transaction_validate_and_validate_all() {
+        let deposit = Transaction {
+            tx_type: TxType::Deposit,
+            ..transfer(0, 42)
+        };
+        let withdrawal = Transaction {
+            tx_id: 8,
+            tx_type: TxType::Withdrawal,
+            ..transfer(42, 0)
+        };
+        assert_eq!(validate_all(&[deposit.clone(), withdrawal.clone()]), Ok(()));
+
+        let bad_deposit = Transaction {
+            from_user_id: 5,
+            ..deposit.clone()
+        };
+        assert_eq!(
+            bad_deposit.validate().unwrap_err().to_string(),
+            "tx 7: DEPOSIT must have from_user_id == 0"
+        );
+        let bad_withdrawal = Transaction {
+            to_user_id: 5,
+            ..withdrawal.clone()
+        };
+        assert_eq!(
+            bad_withdrawal.validate().unwrap_err().rule,
+            "WITHDRAWAL must have to_user_id == 0"
+        );
+        let negative = Transaction {
+            amount: -1,
+            ..transfer(10, 20)
+        };
+        assert_eq!(negative.validate().unwrap_err().rule, "amount must be >= 0");
+
+        let err = validate_all(&[deposit.clone(), withdrawal, deposit]).unwrap_err();
+        assert_eq!(err.to_string(), "tx 7: duplicate tx_id");
+    }
+
+    #[test]
+    fn test_reject_zero_transfer_party() {
+        let strict = ValidationOptions {
+            reject_zero_transfer_party: true,
+            ..ValidationOptions::default()
+        };
+        let leaked = [transfer(10, 0)];
+
+        assert!(validate(&leaked, &ValidationOptions::default()).is_ok());
+        match validate(&leaked, &strict) {
+            Err(BankFormatError::Validation(msg)) => {
+                assert_eq!(msg, "tx 7: TRANSFER must not have to_user_id == 0")
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        let deposit = Transaction {
+            tx_type: TxType::Deposit,
+            ..transfer(0, 42)
+        };
+        assert!(validate(&[transfer(10, 20), deposit], &strict).is_ok());
+    }
+
+    #[test]
+    fn test_reject_self_transfers() {
+        let strict = ValidationOptions {
+            reject_self_transfers: true,
+            ..ValidationOptions::default()
+        };
+        assert!(validate(&[transfer(10, 10)], &ValidationOptions::default()).is_ok());
+        match validate(&[transfer(10, 20), transfer(10, 10)], &strict) {
+            Err(BankFormatError::Validation(msg)) => {
+                assert_eq!(msg, "tx 7: TRANSFER must have from_user_id != to_user_id")
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convention_violations() {
+        let deposit = |tx_id, from_user_id| Transaction {
+            tx_id,
+            tx_type: TxType::Deposit,
+            ..transfer(from_user_id, 42)
+        };
+        let withdrawal = |tx_id, to_user_id| Transaction {
+            tx_id,
+            tx_type: TxType::Withdrawal,
+            ..transfer(42, to_user_id)
+        };
+        let txs = vec![
+            deposit(1, 0),
+            deposit(2, 5),
+            withdrawal(3, 0),
+            withdrawal(4, 9),
+            Transaction {
+                tx_id: 5,
+                ..transfer(0, 0)
+            },
+            Transaction {
+                tx_id: 6,
+                ..transfer(10, 20)
+            },
+        ];
+
+        assert_eq!(
+            convention_violations(&txs),
+            vec![
+                (2, DEPOSIT_FROM_SYSTEM.to_string()),
+                (4, WITHDRAWAL_TO_SYSTEM.to_string()),
+                (5, "TRANSFER must not have from_user_id == 0".to_string()),
+                (5, "TRANSFER must not have to_user_id == 0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plausible_timestamp_range() {
+        let opts = ValidationOptions {
+            plausible_timestamp_range: Some(DEFAULT_PLAUSIBLE_TIMESTAMP_RANGE),
+            ..ValidationOptions::default()
+        };
+        let at = |timestamp| Transaction {
+            timestamp,
+            ..transfer(10, 20)
+        };
+
+        // 2023-11-14 in milliseconds and the same instant in seconds
+        assert!(validate(&[at(1_700_000_000_000)], &opts).is_ok());
+        assert!(matches!(
+            validate(&[at(1_700_000_000)], &opts),
+            Err(BankFormatError::Validation(_))
+        ));
+        assert!(validate(&[at(1_700_000_000)], &ValidationOptions::default()).is_ok());
+    }
+}